@@ -43,14 +43,18 @@ raw_data = sys.stdin.buffer.read()
 #with open("test1.dat", "rb") as f:
 #	raw_data = f.read()
 print(f"Transfering and displaying... ({len(raw_data)} bytes)")
-simulation_data = raw_data[8:]
+simulation_data = raw_data[28:]
 #print(raw_data[-5:])
 #print(raw_data[90800:90840])
 
-latice_density:float = struct.unpack("f", raw_data[:4])[0]
+# Header: node count per axis (u32 x3), latice density per axis (f32 x3), time between frames (f32)
+dimensions:tuple[int, int, int] = struct.unpack("<3I", raw_data[:12])
+latice_density:tuple[float, float, float] = struct.unpack("<3f", raw_data[12:24])
 
-latice_spacing:float = 1.0/latice_density
-dt:float = struct.unpack("f", raw_data[4:8])[0]
+latice_spacing_x:float = 1.0/latice_density[0]
+latice_spacing_y:float = 1.0/latice_density[1]
+latice_spacing_z:float = 1.0/latice_density[2]
+dt:float = struct.unpack("<f", raw_data[24:28])[0]
 #print(len(simulation_data))
 #print(latice_density)
 
@@ -125,13 +129,13 @@ for i in range(0, len(simulation_data), 5):
 	if deliminator >= 1:
 		targeting_E = not targeting_E
 	if deliminator >= 2:
-		x_current += latice_spacing
+		x_current += latice_spacing_x
 	if deliminator >= 3:
 		x_current = 0
-		y_current += latice_spacing
+		y_current += latice_spacing_y
 	if deliminator >= 4:
 		y_current = 0
-		z_current += latice_spacing
+		z_current += latice_spacing_z
 	if deliminator >= 5:
 		#print("5 deliminator!")
 		frames.append(Frame(xE, yE, zE, uE, vE, wE, xB, yB, zB, uB, vB, wB))
@@ -0,0 +1,245 @@
+use std::{f64::consts::PI, io::{self, Write}};
+use crate::{Field3Vec, Latice};
+use crate::solver::Solver;
+use crate::source::{CurrentObject, DrivenComponents, SourceContext};
+use crate::waveform::Waveform;
+
+
+// Nodes in each arm of a dipole `length` long along an axis of `density`, the gap between the
+// arms taking one node
+pub fn arm_nodes(length: f32, density: f32) -> usize {
+    ((length * density - 1.0) / 2.0).round().max(0.0) as usize
+}
+
+// Nodes of a dipole fed at `feed`, arms first and the gap last
+pub fn dipole_cells(feed: (usize, usize, usize), axis: usize, arm_nodes: usize) -> Vec<(usize, usize, usize)> {
+    let feed_position = [feed.0, feed.1, feed.2];
+    let mut cells: Vec<_> = (1..=arm_nodes).flat_map(|offset| [feed_position[axis] - offset, feed_position[axis] + offset]).map(|along| {
+        let mut position = feed_position;
+        position[axis] = along;
+        (position[0], position[1], position[2])
+    }).collect();
+    cells.push(feed);
+    cells
+}
+
+
+// Perfectly conducting thin wire dipole along `axis`, driven by a voltage across the gap at `feed`.
+// The voltage is that of the arm past the gap with respect to the one before it.
+#[derive(Clone)]
+pub struct Dipole {
+    pub feed: (usize, usize, usize),
+    pub axis: usize,
+    pub voltage: f32,
+    pub waveform: Waveform,
+    // Length of the gap, one latice spacing along the axis
+    pub gap: f32
+}
+impl CurrentObject for Dipole {
+    fn current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+
+    fn imposed_e(&self, context: &SourceContext) -> Option<Field3Vec> {
+        let mut e = context.e;
+        e.components[self.axis] = if context.position == self.feed {
+            -self.voltage * self.waveform.value(context.t) / self.gap
+        } else {
+            0.0
+        };
+        Some(e)
+    }
+
+    fn driven_components(&self, _position: (usize, usize, usize)) -> DrivenComponents {
+        let mut e = [false; 3];
+        e[self.axis] = true;
+        DrivenComponents { e, b: [false; 3] }
+    }
+}
+
+
+pub struct PortSample {
+    pub time: f32,
+    pub voltage: f32,
+    // The staggered solver knows the current half a step before the voltage
+    pub current_time: f32,
+    pub current: f32
+}
+
+// Voltage and current across the gap of a dipole, recorded every step
+pub struct Port {
+    pub feed: (usize, usize, usize),
+    pub axis: usize,
+    pub samples: Vec<PortSample>
+}
+
+// Input impedance and reflection of a port at one frequency, as (real, imaginary) pairs
+pub struct PortResponse {
+    pub angular_frequency: f32,
+    pub impedance: (f64, f64),
+    pub s11: (f64, f64)
+}
+impl PortResponse {
+    // In dB, positive when less is reflected
+    pub fn return_loss(&self) -> f64 {
+        -20.0 * self.s11.0.hypot(self.s11.1).log10()
+    }
+}
+
+impl Port {
+    pub fn new(feed: (usize, usize, usize), axis: usize) -> Self {
+        Self { feed, axis, samples: Vec::new() }
+    }
+
+    // Samples the port once the fields have been advanced to `time`
+    pub fn record(&mut self, latice: &Latice, solver: &Solver, latice_density: [f32; 3], m0: f32, time: f32, dt: f32) {
+        let axis = self.axis;
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let spacing = latice_density.map(|density| 1.0 / density);
+        let at = |offset: [isize; 3]| {
+            let position = [0, 1, 2].map(|i| (self.feed_position()[i] as isize + offset[i]) as usize);
+            latice[(position[0], position[1], position[2])].b
+        };
+        let step = |along: usize, sign: isize| {
+            let mut offset = [0; 3];
+            offset[along] = sign;
+            offset
+        };
+        let voltage = -latice[self.feed].e.components[axis] * spacing[axis];
+        // Circulation of H around the gap, counterclockwise seen from the positive axis
+        let (circulation, current_time) = match solver {
+            // Around the gap's own edge, through the B half a node either side of it
+            Solver::Yee => (
+                (at([0; 3]).components[v] - at(step(u, -1)).components[v]) * spacing[v]
+                    - (at([0; 3]).components[u] - at(step(v, -1)).components[u]) * spacing[u],
+                time - 0.5 * dt
+            ),
+            // Central differences of B over the gap node's cell
+            Solver::Collocated => (
+                0.5 * (at(step(u, 1)).components[v] - at(step(u, -1)).components[v]) * spacing[v]
+                    - 0.5 * (at(step(v, 1)).components[u] - at(step(v, -1)).components[u]) * spacing[u],
+                time
+            )
+        };
+        self.samples.push(PortSample { time, voltage, current_time, current: circulation / m0 });
+    }
+
+    fn feed_position(&self) -> [usize; 3] {
+        [self.feed.0, self.feed.1, self.feed.2]
+    }
+
+    // Z = V / I and S11 = (Z - Z0) / (Z + Z0) from the Fourier transforms of the recorded voltage
+    // and current, each evaluated at the times it was sampled at. A direct DFT rather than an FFT:
+    // it is only wanted at the requested frequencies, which needn't fall on an FFT's bins, and the
+    // current is sampled half a step off the voltage on the staggered solver. Where no current
+    // flows the port is an open circuit, Z infinite and everything reflected.
+    pub fn response(&self, angular_frequencies: &[f32], reference_impedance: f32) -> Vec<PortResponse> {
+        angular_frequencies.iter().map(|&angular_frequency| {
+            let transform = |sample: fn(&PortSample) -> (f32, f32)| {
+                self.samples.iter().fold((0.0f64, 0.0f64), |(re, im), port_sample| {
+                    let (time, value) = sample(port_sample);
+                    let phase = -(angular_frequency as f64) * time as f64;
+                    (re + value as f64 * phase.cos(), im + value as f64 * phase.sin())
+                })
+            };
+            let voltage = transform(|sample| (sample.time, sample.voltage));
+            let current = transform(|sample| (sample.current_time, sample.current));
+            if current == (0.0, 0.0) {
+                return PortResponse { angular_frequency, impedance: (f64::INFINITY, 0.0), s11: (1.0, 0.0) };
+            }
+            let impedance = divide(voltage, current);
+            let z0 = reference_impedance as f64;
+            let s11 = divide((impedance.0 - z0, impedance.1), (impedance.0 + z0, impedance.1));
+            PortResponse { angular_frequency, impedance, s11 }
+        }).collect()
+    }
+
+    pub fn write_samples(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "time,voltage,current_time,current")?;
+        for sample in &self.samples {
+            writeln!(writer, "{},{},{},{}", sample.time, sample.voltage, sample.current_time, sample.current)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_response(&self, writer: &mut impl Write, angular_frequencies: &[f32], reference_impedance: f32) -> io::Result<()> {
+        writeln!(writer, "angular_frequency,frequency,resistance,reactance,s11_magnitude,s11_phase,return_loss_db")?;
+        for response in self.response(angular_frequencies, reference_impedance) {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                response.angular_frequency,
+                response.angular_frequency as f64 / (2.0 * PI),
+                response.impedance.0,
+                response.impedance.1,
+                response.s11.0.hypot(response.s11.1),
+                response.s11.1.atan2(response.s11.0),
+                response.return_loss()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn divide((a, b): (f64, f64), (c, d): (f64, f64)) -> (f64, f64) {
+    let norm = c * c + d * d;
+    ((a * c + b * d) / norm, (b * c - a * d) / norm)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Gaussian voltage pulse, and the current `current` makes of it at its own sampling time
+    fn port(dt: f32, current_offset: f32, current: impl Fn(f32) -> f32) -> Port {
+        let mut port = Port::new((1, 1, 1), 2);
+        for step in 0..400 {
+            let time = step as f32 * dt;
+            let current_time = time - current_offset;
+            let voltage = (-((time - 1.0) / 0.2).powi(2)).exp();
+            port.samples.push(PortSample { time, voltage, current_time, current: current(voltage) });
+        }
+        port
+    }
+
+    fn assert_close((re, im): (f64, f64), (expected_re, expected_im): (f64, f64)) {
+        assert!((re - expected_re).abs() < 1e-6 && (im - expected_im).abs() < 1e-6, "({re}, {im}) against ({expected_re}, {expected_im})");
+    }
+
+    #[test]
+    fn resistive_port() {
+        let port = port(0.01, 0.0, |voltage| voltage / 75.0);
+        for response in port.response(&[1.0, 5.0, 10.0], 50.0) {
+            assert!((response.impedance.0 - 75.0).abs() < 1e-3 && response.impedance.1.abs() < 1e-3);
+            assert_close(response.s11, (0.2, 0.0));
+        }
+        // Matched
+        for response in port.response(&[5.0], 75.0) {
+            assert!(response.s11.0.hypot(response.s11.1) < 1e-5);
+        }
+    }
+
+    // The current half a step behind the voltage, as on the staggered solver, while its values are
+    // those of the voltage at its own time: a delay of half a step, Z = R e^(-i w dt / 2)
+    #[test]
+    fn current_sampled_off_the_voltage() {
+        let dt = 0.01;
+        let port = port(dt, 0.5 * dt, |voltage| voltage / 30.0);
+        for response in port.response(&[2.0, 20.0], 50.0) {
+            let phase = -(response.angular_frequency as f64) * 0.5 * dt as f64;
+            let impedance = (30.0 * phase.cos(), 30.0 * phase.sin());
+            assert!((response.impedance.0 - impedance.0).abs() < 1e-3 && (response.impedance.1 - impedance.1).abs() < 1e-3);
+            assert_close(response.s11, divide((impedance.0 - 50.0, impedance.1), (impedance.0 + 50.0, impedance.1)));
+        }
+    }
+
+    #[test]
+    fn open_port() {
+        let port = port(0.01, 0.0, |_| 0.0);
+        let response = &port.response(&[5.0], 50.0)[0];
+        assert_eq!(response.impedance, (f64::INFINITY, 0.0));
+        assert_eq!(response.s11, (1.0, 0.0));
+        assert_eq!(response.return_loss(), 0.0);
+    }
+}
@@ -0,0 +1,619 @@
+use std::fmt;
+use serde::{de::{self, DeserializeOwned}, Deserialize, Deserializer};
+use serde_json::Value;
+use crate::{Field3Vec, Latice, SpaceData};
+use crate::material::Material;
+
+
+pub enum BoundaryCondition {
+    Clip,
+    Fit,
+    // Convolutional perfectly matched layer, the latice past the layer is clipped
+    Cpml(CpmlParameters),
+    // Leaving through one face re-enters through the opposite one
+    Periodic,
+    // Periodic with a phase shift (radians) accumulated over one period
+    Bloch(f32),
+    // Perfect electric conductor, tangential E vanishes on the face
+    Pec,
+    // Perfect magnetic conductor, tangential B vanishes on the face
+    Pmc
+}
+impl BoundaryCondition {
+    // Whether the fields must be carried as complex values
+    pub fn is_complex(&self) -> bool {
+        matches!(self, BoundaryCondition::Bloch(phase) if *phase != 0.0)
+    }
+}
+
+// Conditions for each face of the latice, indexed by axis then lower/upper face
+pub type BoundaryConditions = [[BoundaryCondition; 2]; 3];
+
+const AXIS_NAMES: [&str; 3] = ["x", "y", "z"];
+const FACE_NAMES: [[&str; 2]; 3] = [["x_low", "x_high"], ["y_low", "y_high"], ["z_low", "z_high"]];
+
+
+// A face's condition as the manifest gives it, an object with a "type" and its parameters or, for
+// conditions without any, the bare name ("clip")
+#[derive(Clone)]
+pub enum FaceCondition {
+    Clip,
+    Fit,
+    Cpml(CpmlParameters),
+    Periodic,
+    // Either the phase (radians) accumulated over one period or the wavevector along the axis
+    Bloch {
+        phase: Option<f32>,
+        wavevector: Option<f32>
+    },
+    Pec,
+    Pmc
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FaceType {
+    Clip,
+    Fit,
+    #[serde(alias = "pml")]
+    Cpml,
+    Periodic,
+    Bloch,
+    Pec,
+    Pmc
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlochParameters {
+    phase: Option<f32>,
+    wavevector: Option<f32>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParameters {}
+
+impl FaceCondition {
+    // `parameters` is the rest of the face's object, and errors name the parameter at fault
+    fn from_parameters(face_type: FaceType, parameters: serde_json::Map<String, Value>) -> Result<Self, String> {
+        fn read<T: DeserializeOwned>(parameters: serde_json::Map<String, Value>) -> Result<T, String> {
+            serde_path_to_error::deserialize(Value::Object(parameters)).map_err(|error| format!("{}: {}", error.path(), error.inner()))
+        }
+        let no_parameters = |condition| read::<NoParameters>(parameters.clone()).map(|_| condition);
+        match face_type {
+            FaceType::Clip => no_parameters(FaceCondition::Clip),
+            FaceType::Fit => no_parameters(FaceCondition::Fit),
+            FaceType::Cpml => read(parameters).map(FaceCondition::Cpml),
+            FaceType::Periodic => no_parameters(FaceCondition::Periodic),
+            FaceType::Bloch => {
+                let BlochParameters { phase, wavevector } = read(parameters)?;
+                Ok(FaceCondition::Bloch { phase, wavevector })
+            }
+            FaceType::Pec => no_parameters(FaceCondition::Pec),
+            FaceType::Pmc => no_parameters(FaceCondition::Pmc)
+        }
+    }
+
+    fn from_object<E: de::Error>(mut object: serde_json::Map<String, Value>) -> Result<Self, E> {
+        let face_type = object.remove("type").ok_or_else(|| E::missing_field("type"))?;
+        let face_type = FaceType::deserialize(face_type).map_err(|error| E::custom(format!("type: {error}")))?;
+        Self::from_parameters(face_type, object).map_err(E::custom)
+    }
+
+    // `period` is the physical length of the axis the condition is applied to
+    fn condition(&self, period: f32) -> Result<BoundaryCondition, String> {
+        Ok(match *self {
+            FaceCondition::Clip => BoundaryCondition::Clip,
+            FaceCondition::Fit => BoundaryCondition::Fit,
+            FaceCondition::Cpml(ref parameters) => BoundaryCondition::Cpml(parameters.clone()),
+            FaceCondition::Periodic => BoundaryCondition::Periodic,
+            FaceCondition::Bloch { phase: Some(phase), wavevector: None } => BoundaryCondition::Bloch(phase),
+            FaceCondition::Bloch { phase: None, wavevector: Some(wavevector) } => BoundaryCondition::Bloch(wavevector * period),
+            FaceCondition::Bloch { .. } => return Err("a bloch boundary takes either a phase or a wavevector".to_string()),
+            FaceCondition::Pec => BoundaryCondition::Pec,
+            FaceCondition::Pmc => BoundaryCondition::Pmc
+        })
+    }
+}
+impl<'de> Deserialize<'de> for FaceCondition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FaceConditionVisitor;
+        impl<'de> de::Visitor<'de> for FaceConditionVisitor {
+            type Value = FaceCondition;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a boundary condition name or an object with its \"type\"")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<FaceCondition, E> {
+                let face_type = FaceType::deserialize(de::value::StrDeserializer::<E>::new(name))?;
+                FaceCondition::from_parameters(face_type, serde_json::Map::new()).map_err(E::custom)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<FaceCondition, A::Error> {
+                FaceCondition::from_object(serde_json::Map::deserialize(de::value::MapAccessDeserializer::new(map))?)
+            }
+        }
+        deserializer.deserialize_any(FaceConditionVisitor)
+    }
+}
+
+// The manifest's boundary condition, for each face like `BoundaryConditions`. Given either as a
+// single condition used on every face or as an object keyed by axis ("x") or face ("x_low"), a
+// face's own entry taking precedence.
+#[derive(Clone)]
+pub struct FaceConditions(pub [[FaceCondition; 2]; 3]);
+impl FaceConditions {
+    fn uniform(condition: FaceCondition) -> Self {
+        Self([0, 1, 2].map(|_| [condition.clone(), condition.clone()]))
+    }
+
+    // `periods` are the physical lengths of the axes, which Bloch wavevectors are turned into phases with
+    pub fn resolve(&self, periods: [f32; 3]) -> Result<BoundaryConditions, String> {
+        let mut conditions = [0, 1, 2].map(|_| [BoundaryCondition::Clip, BoundaryCondition::Clip]);
+        for axis in 0..3 {
+            for face in 0..2 {
+                conditions[axis][face] = self.0[axis][face].condition(periods[axis])
+                    .map_err(|message| format!("the {} face: {message}", FACE_NAMES[axis][face]))?;
+            }
+
+            // Periodic conditions tie both faces of an axis together
+            let [low, high] = &conditions[axis];
+            let compatible = match (low, high) {
+                (BoundaryCondition::Periodic, BoundaryCondition::Periodic) => true,
+                (BoundaryCondition::Bloch(low_phase), BoundaryCondition::Bloch(high_phase)) => low_phase == high_phase,
+                (BoundaryCondition::Periodic | BoundaryCondition::Bloch(_), _) => false,
+                (_, BoundaryCondition::Periodic | BoundaryCondition::Bloch(_)) => false,
+                _ => true
+            };
+            if !compatible {
+                return Err(format!(
+                    "the {} and {} faces must both be periodic, with the same phase if Bloch-periodic",
+                    FACE_NAMES[axis][0], FACE_NAMES[axis][1]
+                ));
+            }
+        }
+        Ok(conditions)
+    }
+}
+impl<'de> Deserialize<'de> for FaceConditions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FaceConditionsVisitor;
+        impl<'de> de::Visitor<'de> for FaceConditionsVisitor {
+            type Value = FaceConditions;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a boundary condition or an object of them keyed by axis or face")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<FaceConditions, E> {
+                FaceCondition::deserialize(de::value::StrDeserializer::<E>::new(name)).map(FaceConditions::uniform)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<FaceConditions, A::Error> {
+                let mut axes: [Option<FaceCondition>; 3] = Default::default();
+                let mut faces: [[Option<FaceCondition>; 2]; 3] = Default::default();
+                // Whatever isn't keyed by axis or face, the parameters of a single condition
+                let mut parameters = serde_json::Map::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    if let Some(axis) = AXIS_NAMES.iter().position(|name| *name == key) {
+                        axes[axis] = Some(map.next_value()?);
+                    } else if let Some(face) = FACE_NAMES.iter().flatten().position(|name| *name == key) {
+                        faces[face / 2][face % 2] = Some(map.next_value()?);
+                    } else {
+                        parameters.insert(key, map.next_value()?);
+                    }
+                }
+
+                if axes.iter().chain(faces.iter().flatten()).all(Option::is_none) {
+                    return FaceCondition::from_object(parameters).map(FaceConditions::uniform);
+                }
+                if let Some(key) = parameters.keys().next() {
+                    return Err(de::Error::custom(format!("unknown boundary `{key}`, expected an axis like `x` or a face like `x_low`")));
+                }
+                let mut conditions = [0, 1, 2].map(|_| [FaceCondition::Clip, FaceCondition::Clip]);
+                for axis in 0..3 {
+                    for face in 0..2 {
+                        conditions[axis][face] = faces[axis][face].take().or_else(|| axes[axis].clone()).ok_or_else(|| {
+                            de::Error::custom(format!("no boundary condition given for the {} face", FACE_NAMES[axis][face]))
+                        })?;
+                    }
+                }
+                Ok(FaceConditions(conditions))
+            }
+        }
+        deserializer.deserialize_any(FaceConditionsVisitor)
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    E,
+    // B over the relative permeability of its node, what curl B in the E update actually needs
+    H
+}
+impl Field {
+    pub fn of(self, node: &SpaceData, materials: &[Material]) -> Field3Vec {
+        match self {
+            Field::E => node.e,
+            Field::H => node.b / materials[node.material_index].permeability
+        }
+    }
+
+    // Whether the components tangential to a face of `condition` are odd under reflection through it
+    fn tangential_is_odd(self, condition: &BoundaryCondition) -> bool {
+        matches!((self, condition), (Field::E, BoundaryCondition::Pec) | (Field::H, BoundaryCondition::Pmc))
+    }
+}
+
+// Mirror image of `value` through a face normal to `axis`: the tangential components flip sign when
+// `tangential_odd`, the normal component otherwise
+fn reflect(value: Field3Vec, axis: usize, tangential_odd: bool) -> Field3Vec {
+    let mut image = value;
+    for (component, value) in image.components.iter_mut().enumerate() {
+        if (component != axis) == tangential_odd {
+            *value = -*value;
+        }
+    }
+    image
+}
+
+
+// Neighbour lookup for the field derivatives, filling in nodes past the latice faces
+pub struct Neighbors<'a> {
+    pub latice: &'a Latice,
+    // Other half of the complex fields under Bloch-periodic conditions
+    pub partner: Option<&'a Latice>,
+    // +1 when `latice` carries the real part of the fields, -1 for the imaginary part
+    pub part_sign: f32,
+    pub boundary_conditions: &'a BoundaryConditions,
+    pub materials: &'a [Material],
+    // Whether the fields sit on a staggered (Yee) grid
+    pub staggered: bool
+}
+impl Neighbors<'_> {
+    pub fn forward(&self, position: [usize; 3], axis: usize, field: Field) -> Field3Vec {
+        let mut neighbor = position;
+        if position[axis] + 1 < self.latice.dimensions[axis] {
+            neighbor[axis] += 1;
+            return field.of(node_at(self.latice, neighbor), self.materials);
+        }
+        neighbor[axis] = 0;
+        self.ghost(position, neighbor, axis, 1.0, field)
+    }
+
+    pub fn backward(&self, position: [usize; 3], axis: usize, field: Field) -> Field3Vec {
+        let mut neighbor = position;
+        if position[axis] > 0 {
+            neighbor[axis] -= 1;
+            return field.of(node_at(self.latice, neighbor), self.materials);
+        }
+        neighbor[axis] = self.latice.dimensions[axis] - 1;
+        self.ghost(position, neighbor, axis, -1.0, field)
+    }
+
+    // `wrapped` is the node on the opposite face, `direction` which way the face was crossed
+    fn ghost(&self, position: [usize; 3], wrapped: [usize; 3], axis: usize, direction: f32, field: Field) -> Field3Vec {
+        let face = if direction > 0.0 { 1 } else { 0 };
+        let mut inner = position;
+        if direction > 0.0 {
+            inner[axis] -= 1;
+        } else {
+            inner[axis] += 1;
+        }
+        let condition = &self.boundary_conditions[axis][face];
+        match condition {
+            BoundaryCondition::Clip | BoundaryCondition::Cpml(_) => Field3Vec::default(),
+            BoundaryCondition::Fit => {
+                // Extrapolate linearly from the two outermost nodes
+                2.0 * field.of(node_at(self.latice, position), self.materials) - field.of(node_at(self.latice, inner), self.materials)
+            }
+            BoundaryCondition::Periodic => field.of(node_at(self.latice, wrapped), self.materials),
+            BoundaryCondition::Bloch(phase) => {
+                let value = field.of(node_at(self.latice, wrapped), self.materials);
+                match self.partner {
+                    // F(x + L) = F(x) e^(i phase), keeping the part this latice carries
+                    Some(partner) => {
+                        let (sin, cos) = (direction * phase).sin_cos();
+                        cos * value - self.part_sign * sin * field.of(node_at(partner, wrapped), self.materials)
+                    }
+                    None => value
+                }
+            }
+            BoundaryCondition::Pec | BoundaryCondition::Pmc => {
+                // The face passes through the outermost node. On the Yee grid the tangential B of the
+                // outermost node sits half a node inside the lower face, so it is its own image.
+                let mirror = if self.staggered && field != Field::E { position } else { inner };
+                reflect(field.of(node_at(self.latice, mirror), self.materials), axis, field.tangential_is_odd(condition))
+            }
+        }
+    }
+}
+
+// Zero the components of E held at zero by conductor faces, after E has been advanced
+pub fn enforce_e(latice: &mut Latice, boundary_conditions: &BoundaryConditions, staggered: bool) {
+    for_each_face_node(latice, boundary_conditions, |latice, position, axis, _, condition| {
+        let node = &mut latice[(position[0], position[1], position[2])];
+        match condition {
+            BoundaryCondition::Pec => node.e = tangential_zeroed(node.e, axis),
+            // The staggered normal E never lies on the face
+            BoundaryCondition::Pmc if !staggered => node.e.components[axis] = 0.0,
+            _ => ()
+        }
+    });
+}
+
+// Counterpart of `enforce_e` for B, after B has been advanced
+pub fn enforce_b(latice: &mut Latice, boundary_conditions: &BoundaryConditions, staggered: bool) {
+    for_each_face_node(latice, boundary_conditions, |latice, position, axis, face, condition| {
+        match condition {
+            BoundaryCondition::Pec if !staggered => latice[(position[0], position[1], position[2])].b.components[axis] = 0.0,
+            BoundaryCondition::Pmc if !staggered => {
+                let node = &mut latice[(position[0], position[1], position[2])];
+                node.b = tangential_zeroed(node.b, axis);
+            }
+            // The outermost tangential B sits half a node past the upper face, mirror the one inside
+            BoundaryCondition::Pmc if face == 1 => {
+                let mut inner = position;
+                inner[axis] -= 1;
+                let image = reflect(node_at(latice, inner).b, axis, true);
+                let node = &mut latice[(position[0], position[1], position[2])];
+                for component in 0..3 {
+                    if component != axis {
+                        node.b.components[component] = image.components[component];
+                    }
+                }
+            }
+            _ => ()
+        }
+    });
+}
+
+fn tangential_zeroed(value: Field3Vec, axis: usize) -> Field3Vec {
+    let mut zeroed = Field3Vec::default();
+    zeroed.components[axis] = value.components[axis];
+    zeroed
+}
+
+// Calls `apply` with every node on a face whose condition is a conductor
+fn for_each_face_node(
+    latice: &mut Latice,
+    boundary_conditions: &BoundaryConditions,
+    mut apply: impl FnMut(&mut Latice, [usize; 3], usize, usize, &BoundaryCondition)
+) {
+    let dimensions = latice.dimensions;
+    for (axis, faces) in boundary_conditions.iter().enumerate() {
+        for (face, condition) in faces.iter().enumerate() {
+            if !matches!(condition, BoundaryCondition::Pec | BoundaryCondition::Pmc) {
+                continue;
+            }
+            let (axis_i, axis_j) = ((axis + 1) % 3, (axis + 2) % 3);
+            for i in 0..dimensions[axis_i] {
+                for j in 0..dimensions[axis_j] {
+                    let mut position = [0; 3];
+                    position[axis] = if face == 0 { 0 } else { dimensions[axis] - 1 };
+                    position[axis_i] = i;
+                    position[axis_j] = j;
+                    apply(latice, position, axis, face, condition);
+                }
+            }
+        }
+    }
+}
+
+fn node_at(latice: &Latice, [x, y, z]: [usize; 3]) -> &SpaceData {
+    &latice[(x, y, z)]
+}
+
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpmlParameters {
+    // Layer depth in latice nodes
+    pub thickness: usize,
+    // Polynomial grading order of the conductivity profile
+    pub order: f32,
+    // Target reflection coefficient at normal incidence
+    pub reflection: f32,
+    pub kappa_max: f32,
+    pub alpha_max: f32
+}
+impl Default for CpmlParameters {
+    fn default() -> Self {
+        Self {
+            thickness: 10,
+            order: 3.0,
+            reflection: 1e-6,
+            kappa_max: 1.0,
+            alpha_max: 0.0
+        }
+    }
+}
+impl CpmlParameters {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.thickness == 0 {
+            return Err("thickness must be positive".to_string());
+        }
+        // Written so NaN fails too
+        if !(self.reflection > 0.0 && self.reflection < 1.0) {
+            return Err(format!("reflection must be between 0 and 1, found {}", self.reflection));
+        }
+        if !(self.kappa_max >= 1.0 && self.kappa_max.is_finite()) {
+            return Err(format!("kappa_max must be at least 1, found {}", self.kappa_max));
+        }
+        Ok(())
+    }
+}
+
+
+// Recursive convolution coefficients along one axis, indexed by node position
+struct CpmlProfile {
+    inverse_kappa: Vec<f32>,
+    b: Vec<f32>,
+    c: Vec<f32>
+}
+impl CpmlProfile {
+    // `layers` holds the lower and upper face's layer, `offset` shifts the sampling points,
+    // half a node for the staggered E derivatives of the Yee solver
+    fn new(layers: [Option<&CpmlParameters>; 2], size: usize, density: f32, dt: f32, speed_of_light: f32, offset: f32) -> Self {
+        let mut profile = Self {
+            inverse_kappa: vec![1.0; size],
+            b: vec![0.0; size],
+            c: vec![0.0; size]
+        };
+
+        for i in 0..size {
+            let position = i as f32 + offset;
+            let (parameters, depth) = match layers {
+                [Some(low), _] if position < low.thickness as f32 => {
+                    (low, (low.thickness as f32 - position) / low.thickness as f32)
+                }
+                [_, Some(high)] if position > (size - 1 - high.thickness) as f32 => {
+                    (high, (position - (size - 1 - high.thickness) as f32) / high.thickness as f32)
+                }
+                _ => continue
+            };
+            let depth = depth.min(1.0);
+            // Conductivity (normalized by the permittivity) giving the requested reflection at normal incidence
+            let sigma_max = -(parameters.order + 1.0) * parameters.reflection.ln() * speed_of_light * density
+                / (2.0 * parameters.thickness as f32);
+
+            let graded = depth.powf(parameters.order);
+            let sigma = sigma_max * graded;
+            let kappa = 1.0 + (parameters.kappa_max - 1.0) * graded;
+            let alpha = parameters.alpha_max * (1.0 - depth);
+
+            profile.inverse_kappa[i] = 1.0 / kappa;
+            profile.b[i] = (-(sigma / kappa + alpha) * dt).exp();
+            if sigma > 0.0 {
+                profile.c[i] = sigma / (sigma * kappa + kappa * kappa * alpha) * (profile.b[i] - 1.0);
+            }
+        }
+        profile
+    }
+}
+
+
+pub struct Cpml {
+    e_profiles: [CpmlProfile; 3],
+    b_profiles: [CpmlProfile; 3],
+    // Convolution memory of the derivative along each axis, one entry per latice node
+    psi_e: [Vec<Field3Vec>; 3],
+    psi_b: [Vec<Field3Vec>; 3]
+}
+impl Cpml {
+    // Returns None when no face has an absorbing layer
+    pub fn new(
+        boundary_conditions: &BoundaryConditions,
+        dimensions: [usize; 3],
+        latice_density: [f32; 3],
+        dt: f32,
+        speed_of_light: f32,
+        e_derivative_offset: f32
+    ) -> Option<Self> {
+        let layers = boundary_conditions.each_ref().map(|faces| faces.each_ref().map(|condition| match condition {
+            BoundaryCondition::Cpml(parameters) => Some(parameters),
+            _ => None
+        }));
+        if layers.iter().flatten().all(Option::is_none) {
+            return None;
+        }
+        let cells = dimensions[0] * dimensions[1] * dimensions[2];
+        Some(Self {
+            e_profiles: [0, 1, 2].map(|axis| CpmlProfile::new(
+                layers[axis], dimensions[axis], latice_density[axis], dt, speed_of_light, e_derivative_offset
+            )),
+            b_profiles: [0, 1, 2].map(|axis| CpmlProfile::new(
+                layers[axis], dimensions[axis], latice_density[axis], dt, speed_of_light, 0.0
+            )),
+            // Only axes with a layer ever touch their convolution memory
+            psi_e: layers.map(|faces| vec![Field3Vec::default(); if faces.iter().any(Option::is_some) { cells } else { 0 }]),
+            psi_b: layers.map(|faces| vec![Field3Vec::default(); if faces.iter().any(Option::is_some) { cells } else { 0 }])
+        })
+    }
+
+    // Replace the derivative of E along `axis` with its stretched-coordinate counterpart
+    pub fn stretch_e(&mut self, axis: usize, cell: usize, coordinate: usize, derivative: Field3Vec) -> Field3Vec {
+        stretch(&self.e_profiles[axis], &mut self.psi_e[axis], cell, coordinate, derivative)
+    }
+
+    pub fn stretch_b(&mut self, axis: usize, cell: usize, coordinate: usize, derivative: Field3Vec) -> Field3Vec {
+        stretch(&self.b_profiles[axis], &mut self.psi_b[axis], cell, coordinate, derivative)
+    }
+}
+
+fn stretch(profile: &CpmlProfile, psi: &mut [Field3Vec], cell: usize, coordinate: usize, derivative: Field3Vec) -> Field3Vec {
+    let c = profile.c[coordinate];
+    if c == 0.0 {
+        return derivative;
+    }
+    psi[cell] = profile.b[coordinate] * psi[cell] + c * derivative;
+    derivative * profile.inverse_kappa[coordinate] + psi[cell]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn faces(value: Value) -> Result<FaceConditions, String> {
+        serde_json::from_value(value).map_err(|error| error.to_string())
+    }
+
+    fn resolve(value: Value) -> Result<BoundaryConditions, String> {
+        faces(value)?.resolve([1.0, 2.0, 4.0])
+    }
+
+    #[test]
+    fn single_condition_on_every_face() {
+        let conditions = resolve(json!({ "type": "cpml", "thickness": 3 })).unwrap();
+        assert!(conditions.iter().flatten().all(|face| matches!(face, BoundaryCondition::Cpml(CpmlParameters { thickness: 3, .. }))));
+        assert!(resolve(json!("pec")).unwrap().iter().flatten().all(|face| matches!(face, BoundaryCondition::Pec)));
+    }
+
+    #[test]
+    fn face_overrides_its_axis() {
+        let conditions = resolve(json!({ "x": "pec", "x_high": "pmc", "y": "fit", "z_low": "clip", "z": "pec" })).unwrap();
+        assert!(matches!(conditions[0], [BoundaryCondition::Pec, BoundaryCondition::Pmc]));
+        assert!(matches!(conditions[1], [BoundaryCondition::Fit, BoundaryCondition::Fit]));
+        // Whichever comes first
+        assert!(matches!(conditions[2], [BoundaryCondition::Clip, BoundaryCondition::Pec]));
+    }
+
+    #[test]
+    fn missing_face() {
+        let error = faces(json!({ "x": "pec", "y": "clip", "z_low": "clip" })).err().unwrap();
+        assert_eq!(error, "no boundary condition given for the z_high face");
+    }
+
+    #[test]
+    fn unknown_face() {
+        let error = faces(json!({ "x": "pec", "y": "clip", "z": "clip", "w": "clip" })).err().unwrap();
+        assert!(error.starts_with("unknown boundary `w`"), "{error}");
+        let error = faces(json!({ "x": "pec", "y": "clip", "z": "clip", "type": "clip" })).err().unwrap();
+        assert!(error.starts_with("unknown boundary `type`"), "{error}");
+    }
+
+    #[test]
+    fn periodic_faces_come_in_pairs() {
+        let error = resolve(json!({ "x_low": "periodic", "x_high": "clip", "y": "clip", "z": "clip" })).err().unwrap();
+        assert_eq!(error, "the x_low and x_high faces must both be periodic, with the same phase if Bloch-periodic");
+        let error = resolve(json!({ "x": "clip", "y_low": "pec", "y_high": { "type": "bloch", "phase": 1 }, "z": "clip" })).err().unwrap();
+        assert!(error.starts_with("the y_low and y_high faces"), "{error}");
+        let error = resolve(json!({
+            "x": "clip", "y": "clip", "z_low": { "type": "bloch", "phase": 1 }, "z_high": { "type": "bloch", "phase": 2 }
+        })).err().unwrap();
+        assert!(error.starts_with("the z_low and z_high faces"), "{error}");
+    }
+
+    #[test]
+    fn bloch_wavevector_over_the_period() {
+        let conditions = resolve(json!({ "x": "clip", "y": "clip", "z": { "type": "bloch", "wavevector": 0.5 } })).unwrap();
+        assert!(matches!(conditions[2], [BoundaryCondition::Bloch(2.0), BoundaryCondition::Bloch(2.0)]));
+        let error = resolve(json!({ "x": "clip", "y": "clip", "z": { "type": "bloch", "wavevector": 0.5, "phase": 1 } })).err().unwrap();
+        assert_eq!(error, "the z_low face: a bloch boundary takes either a phase or a wavevector");
+    }
+}
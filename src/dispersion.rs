@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use crate::{Field3Vec, Latice};
+use crate::material::Material;
+
+
+// One term of a material's frequency-dependent susceptibility. Frequencies are angular, like the
+// wires' `angular_frequency`, and the strengths are relative to e0.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Pole {
+    // Free electrons: e0 plasma_frequency^2 / (-w^2 - i collision_frequency w)
+    Drude {
+        plasma_frequency: f32,
+        #[serde(default)]
+        collision_frequency: f32
+    },
+    // Bound resonance: e0 delta_permittivity resonance_frequency^2 / (resonance_frequency^2 - w^2 - i damping w)
+    Lorentz {
+        delta_permittivity: f32,
+        resonance_frequency: f32,
+        #[serde(default)]
+        damping: f32
+    },
+    // Orientational relaxation: e0 delta_permittivity / (1 - i w relaxation_time)
+    Debye {
+        delta_permittivity: f32,
+        relaxation_time: f32
+    }
+}
+impl Pole {
+    pub fn validate(&self) -> Result<(), String> {
+        let parameters = match *self {
+            Pole::Drude { plasma_frequency, collision_frequency } => vec![plasma_frequency, collision_frequency],
+            Pole::Lorentz { delta_permittivity, resonance_frequency, damping } => vec![delta_permittivity, resonance_frequency, damping],
+            Pole::Debye { delta_permittivity, relaxation_time } => {
+                if relaxation_time <= 0.0 || !relaxation_time.is_finite() {
+                    return Err("relaxation_time must be positive".to_string());
+                }
+                vec![delta_permittivity]
+            }
+        };
+        if parameters.iter().any(|&parameter| parameter < 0.0 || !parameter.is_finite()) {
+            return Err("pole parameters cannot be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Clone, Copy, Default)]
+struct PoleState {
+    // Polarization current J = dP/dt, half a step behind E
+    current: Field3Vec,
+    // Polarization P, in step with E (unused by Drude poles)
+    polarization: Field3Vec
+}
+
+// Auxiliary differential equation state of every pole of every dispersive node
+pub struct Dispersion {
+    // Index of each node's first pole state, its material says how many follow
+    offsets: Vec<usize>,
+    states: Vec<PoleState>
+}
+impl Dispersion {
+    // Returns None when no node of the latice is dispersive
+    pub fn new(latice: &Latice, materials: &[Material]) -> Option<Self> {
+        let mut offsets = Vec::with_capacity(latice.cells.len());
+        let mut pole_count = 0;
+        for node in &latice.cells {
+            offsets.push(pole_count);
+            pole_count += materials[node.material_index].poles.len();
+        }
+        if pole_count == 0 {
+            return None;
+        }
+        Some(Self {
+            offsets,
+            states: vec![PoleState::default(); pole_count]
+        })
+    }
+
+    // Advances the poles of a node from E^n and returns their total current J^(n+1/2)
+    pub fn advance(&mut self, cell: usize, poles: &[Pole], e: Field3Vec, dt: f32, e0: f32) -> Field3Vec {
+        let mut total = Field3Vec::default();
+        let states = &mut self.states[self.offsets[cell]..self.offsets[cell] + poles.len()];
+        for (pole, state) in poles.iter().zip(states.iter_mut()) {
+            match *pole {
+                // dJ/dt + collision_frequency J = e0 plasma_frequency^2 E
+                Pole::Drude { plasma_frequency, collision_frequency } => {
+                    let damping = 0.5 * collision_frequency * dt;
+                    state.current = ((1.0 - damping) * state.current + e0 * plasma_frequency.powi(2) * dt * e) / (1.0 + damping);
+                }
+                // dJ/dt + damping J + resonance_frequency^2 P = e0 delta_permittivity resonance_frequency^2 E
+                Pole::Lorentz { delta_permittivity, resonance_frequency, damping } => {
+                    let damping = 0.5 * damping * dt;
+                    let restoring = resonance_frequency.powi(2) * dt * (e0 * delta_permittivity * e - state.polarization);
+                    state.current = ((1.0 - damping) * state.current + restoring) / (1.0 + damping);
+                    state.polarization = state.polarization + dt * state.current;
+                }
+                // relaxation_time dP/dt + P = e0 delta_permittivity E
+                Pole::Debye { delta_permittivity, relaxation_time } => {
+                    let relaxation = 0.5 * dt / relaxation_time;
+                    let polarization = ((1.0 - relaxation) * state.polarization + 2.0 * relaxation * e0 * delta_permittivity * e)
+                        / (1.0 + relaxation);
+                    state.current = (polarization - state.polarization) / dt;
+                    state.polarization = polarization;
+                }
+            }
+            total = total + state.current;
+        }
+        total
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E: Field3Vec = Field3Vec { components: [0.0, 0.0, 1.0] };
+
+    // A single node of a material made of `poles`
+    fn node(poles: Vec<Pole>) -> (Dispersion, Vec<Pole>) {
+        let material = Material { poles: poles.clone(), ..Material::VACUUM };
+        let mut latice = Latice::new([1, 1, 1]);
+        latice.cells[0].material_index = 1;
+        (Dispersion::new(&latice, &[Material::VACUUM, material]).unwrap(), poles)
+    }
+
+    // Polarization built up by `steps` steps under a constant E, the time integral of the current
+    fn polarization(poles: Vec<Pole>, steps: usize, dt: f32, e0: f32) -> f32 {
+        let (mut dispersion, poles) = node(poles);
+        (0..steps).map(|_| dispersion.advance(0, &poles, E, dt, e0).components[2] * dt).sum()
+    }
+
+    #[test]
+    fn vacuum_is_not_dispersive() {
+        assert!(Dispersion::new(&Latice::new([2, 2, 2]), &[Material::VACUUM]).is_none());
+    }
+
+    #[test]
+    fn debye_settles_to_its_static_permittivity() {
+        let debye = || vec![Pole::Debye { delta_permittivity: 3.0, relaxation_time: 0.5 }];
+        let settled = polarization(debye(), 2000, 0.01, 1.5);
+        assert!((settled - 1.5 * 3.0).abs() < 1e-3, "{settled}");
+        // Having relaxed by 1 - 1/e after one relaxation time
+        let relaxed = polarization(debye(), 50, 0.01, 1.5) / settled;
+        assert!((relaxed - (1.0 - (-1f32).exp())).abs() < 1e-3, "{relaxed}");
+    }
+
+    #[test]
+    fn lorentz_settles_to_its_static_permittivity() {
+        let lorentz = vec![Pole::Lorentz { delta_permittivity: 2.0, resonance_frequency: 5.0, damping: 3.0 }];
+        let settled = polarization(lorentz, 5000, 0.01, 1.5);
+        assert!((settled - 1.5 * 2.0).abs() < 1e-3, "{settled}");
+    }
+
+    #[test]
+    fn drude_current_decays_at_the_collision_frequency() {
+        let (mut dispersion, poles) = node(vec![Pole::Drude { plasma_frequency: 10.0, collision_frequency: 4.0 }]);
+        let dt = 0.001;
+        let kicked = dispersion.advance(0, &poles, E, dt, 1.0).components[2];
+        // e0 plasma_frequency^2 E dt, less the half step of collisions
+        assert!((kicked - 100.0 * dt / (1.0 + 2.0 * dt)).abs() < 1e-5 * kicked, "{kicked}");
+        let mut current = kicked;
+        for _ in 0..250 {
+            current = dispersion.advance(0, &poles, Field3Vec::default(), dt, 1.0).components[2];
+        }
+        // 250 steps are one collision time
+        let decayed = current / kicked;
+        assert!((decayed - (-1f32).exp()).abs() < 1e-3, "{decayed}");
+    }
+
+    #[test]
+    fn drude_conducts_a_constant_field() {
+        let (mut dispersion, poles) = node(vec![Pole::Drude { plasma_frequency: 10.0, collision_frequency: 4.0 }]);
+        let mut current = 0.0;
+        for _ in 0..1000 {
+            current = dispersion.advance(0, &poles, E, 0.01, 1.5).components[2];
+        }
+        // The conductivity e0 plasma_frequency^2 / collision_frequency
+        assert!((current - 1.5 * 100.0 / 4.0).abs() < 1e-3 * current, "{current}");
+    }
+
+    #[test]
+    fn poles_add_up() {
+        let poles = vec![
+            Pole::Debye { delta_permittivity: 3.0, relaxation_time: 0.5 },
+            Pole::Lorentz { delta_permittivity: 2.0, resonance_frequency: 5.0, damping: 3.0 }
+        ];
+        let settled = polarization(poles, 5000, 0.01, 1.0);
+        assert!((settled - 5.0).abs() < 1e-3, "{settled}");
+    }
+}
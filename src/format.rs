@@ -0,0 +1,451 @@
+use std::{fmt, io::{self, Read, Write}};
+use serde::Deserialize;
+use crate::{Latice, Simulation, SpaceData};
+use crate::output::OutputSink;
+
+
+/*
+ * Binary field file, every number little endian:
+ *   magic "MAXFIELD", version u32
+ *   stored dimensions 3 x u32, index of the first stored node 3 x u32, stored spacing 3 x f32, time
+ *   between frames f32
+ *   e0 f32, m0 f32, offset of the stored B's time from the frame's time f32
+ *   time culling factor u32, space culling factor u32
+ *   component count u32, then each component's name as a u32 length and UTF-8 bytes
+ *   manifest as a u64 length and UTF-8 bytes, with the steps and time step simulated
+ * then frames until the end of the file:
+ *   step u32, time of E f32, then each component over the whole stored grid, x fastest, as f32
+ */
+pub const MAGIC: &[u8; 8] = b"MAXFIELD";
+pub const VERSION: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum Component {
+    Ex,
+    Ey,
+    Ez,
+    Bx,
+    By,
+    Bz
+}
+impl Component {
+    pub const ALL: [Component; 6] = [Component::Ex, Component::Ey, Component::Ez, Component::Bx, Component::By, Component::Bz];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Component::Ex => "Ex",
+            Component::Ey => "Ey",
+            Component::Ez => "Ez",
+            Component::Bx => "Bx",
+            Component::By => "By",
+            Component::Bz => "Bz"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|component| component.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_electric(self) -> bool {
+        matches!(self, Component::Ex | Component::Ey | Component::Ez)
+    }
+
+    pub fn axis(self) -> usize {
+        match self {
+            Component::Ex | Component::Bx => 0,
+            Component::Ey | Component::By => 1,
+            Component::Ez | Component::Bz => 2
+        }
+    }
+
+    pub fn of(self, node: &SpaceData) -> f32 {
+        match self {
+            Component::Ex => node.e.components[0],
+            Component::Ey => node.e.components[1],
+            Component::Ez => node.e.components[2],
+            Component::Bx => node.b.components[0],
+            Component::By => node.b.components[1],
+            Component::Bz => node.b.components[2]
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Header {
+    // Nodes stored along each axis, after space culling
+    pub dimensions: [usize; 3],
+    // Index of the first stored node, the others following every `space_culling_factor` nodes
+    pub origin: [usize; 3],
+    // Distance between stored nodes along each axis
+    pub spacing: [f32; 3],
+    // Time between the steps frames are usually written at
+    pub frame_dt: f32,
+    pub e0: f32,
+    pub m0: f32,
+    // Time of the stored B relative to the frame's, which is E's: B lags half a step on the Yee
+    // solver
+    pub b_time_offset: f32,
+    pub time_culling_factor: u32,
+    pub space_culling_factor: u32,
+    pub components: Vec<Component>,
+    // Copy of the manifest the file was simulated from, command line overrides included
+    pub manifest: String
+}
+impl Header {
+    // Values in one frame
+    pub fn frame_len(&self) -> usize {
+        self.components.len() * self.dimensions.iter().product::<usize>()
+    }
+}
+
+// Nodes kept by space culling, the same ones the display stream keeps
+pub fn culled_dimensions(dimensions: [usize; 3], space_culling_factor: u32) -> [usize; 3] {
+    dimensions.map(|n| n / space_culling_factor as usize)
+}
+
+// The nodes themselves, x fastest, the last of every `space_culling_factor` along each axis
+pub fn culled_nodes(latice: &Latice, space_culling_factor: u32) -> Vec<&SpaceData> {
+    let factor = space_culling_factor as usize;
+    let [size_x, size_y, size_z] = culled_dimensions(latice.dimensions, space_culling_factor);
+    (0..size_z).flat_map(|z| (0..size_y).flat_map(move |y| (0..size_x).map(move |x| {
+        &latice[((x + 1) * factor - 1, (y + 1) * factor - 1, (z + 1) * factor - 1)]
+    }))).collect()
+}
+
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    // Doesn't start with the magic bytes
+    NotAFieldFile,
+    UnsupportedVersion(u32),
+    Corrupt(String)
+}
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "{error}"),
+            FormatError::NotAFieldFile => write!(f, "not a field file"),
+            FormatError::UnsupportedVersion(version) => write!(f, "field file version {version} is not supported, only {VERSION} is"),
+            FormatError::Corrupt(message) => write!(f, "corrupt field file: {message}")
+        }
+    }
+}
+impl std::error::Error for FormatError {}
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        FormatError::Io(error)
+    }
+}
+
+
+pub struct FrameWriter<W: Write> {
+    writer: W,
+    header: Header
+}
+impl<W: Write> FrameWriter<W> {
+    pub fn new(mut writer: W, header: Header) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for n in header.dimensions.into_iter().chain(header.origin) {
+            writer.write_all(&(n as u32).to_le_bytes())?;
+        }
+        for value in header.spacing.into_iter().chain([header.frame_dt, header.e0, header.m0, header.b_time_offset]) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&header.time_culling_factor.to_le_bytes())?;
+        writer.write_all(&header.space_culling_factor.to_le_bytes())?;
+        writer.write_all(&(header.components.len() as u32).to_le_bytes())?;
+        for component in &header.components {
+            writer.write_all(&(component.name().len() as u32).to_le_bytes())?;
+            writer.write_all(component.name().as_bytes())?;
+        }
+        writer.write_all(&(header.manifest.len() as u64).to_le_bytes())?;
+        writer.write_all(header.manifest.as_bytes())?;
+        Ok(Self { writer, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    // Writes the stored components of the nodes space culling keeps
+    pub fn write_frame(&mut self, step: u32, time: f32, latice: &Latice) -> io::Result<()> {
+        self.writer.write_all(&step.to_le_bytes())?;
+        self.writer.write_all(&time.to_le_bytes())?;
+        let factor = self.header.space_culling_factor as usize;
+        let [size_x, size_y, size_z] = self.header.dimensions;
+        let [origin_x, origin_y, origin_z] = self.header.origin;
+        let mut buffer = Vec::with_capacity(4 * size_x);
+        for component in &self.header.components {
+            for z in 0..size_z {
+                for y in 0..size_y {
+                    buffer.clear();
+                    for x in 0..size_x {
+                        let node = &latice[(origin_x + x * factor, origin_y + y * factor, origin_z + z * factor)];
+                        buffer.extend_from_slice(&component.of(node).to_le_bytes());
+                    }
+                    self.writer.write_all(&buffer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+impl<W: Write> OutputSink for FrameWriter<W> {
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        self.write_frame(simulation.steps_taken(), simulation.time(), simulation.latice())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+
+pub struct Frame {
+    pub step: u32,
+    // Time of E, see `b_time` for B's
+    pub time: f32,
+    // One grid per stored component, x fastest
+    pub values: Vec<Vec<f32>>
+}
+impl Frame {
+    pub fn value(&self, header: &Header, component: usize, (x, y, z): (usize, usize, usize)) -> f32 {
+        let [size_x, size_y, _] = header.dimensions;
+        self.values[component][(z * size_y + y) * size_x + x]
+    }
+
+    pub fn b_time(&self, header: &Header) -> f32 {
+        self.time + header.b_time_offset
+    }
+}
+
+pub struct FrameReader<R: Read> {
+    reader: R,
+    header: Header
+}
+impl<R: Read> FrameReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, FormatError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(FormatError::NotAFieldFile);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let dimensions = [read_u32(&mut reader)?, read_u32(&mut reader)?, read_u32(&mut reader)?].map(|n| n as usize);
+        let origin = [read_u32(&mut reader)?, read_u32(&mut reader)?, read_u32(&mut reader)?].map(|n| n as usize);
+        let spacing = [read_f32(&mut reader)?, read_f32(&mut reader)?, read_f32(&mut reader)?];
+        let frame_dt = read_f32(&mut reader)?;
+        let e0 = read_f32(&mut reader)?;
+        let m0 = read_f32(&mut reader)?;
+        let b_time_offset = read_f32(&mut reader)?;
+        let time_culling_factor = read_u32(&mut reader)?;
+        let space_culling_factor = read_u32(&mut reader)?;
+        let component_count = read_u32(&mut reader)?;
+        let mut components = Vec::new();
+        for _ in 0..component_count {
+            let length = read_u32(&mut reader)? as usize;
+            let name = read_string(&mut reader, length)?;
+            components.push(Component::from_name(&name).ok_or_else(|| FormatError::Corrupt(format!("unknown component \"{name}\"")))?);
+        }
+        let manifest_length = u64::from_le_bytes(read_array(&mut reader)?) as usize;
+        let manifest = read_string(&mut reader, manifest_length)?;
+        let header = Header {
+            dimensions, origin, spacing, frame_dt, e0, m0, b_time_offset, time_culling_factor, space_culling_factor, components, manifest
+        };
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    // None once the file has ended between frames
+    pub fn read_frame(&mut self) -> Result<Option<Frame>, FormatError> {
+        let mut step = [0; 4];
+        match self.reader.read_exact(&mut step) {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into())
+        }
+        let mut time = [0; 4];
+        self.reader.read_exact(&mut time).map_err(|_| FormatError::Corrupt("the last frame is cut short".to_string()))?;
+        let grid_len = self.header.dimensions.iter().product::<usize>();
+        let mut bytes = vec![0; 4 * grid_len];
+        let mut values = Vec::with_capacity(self.header.components.len());
+        for _ in &self.header.components {
+            self.reader.read_exact(&mut bytes).map_err(|_| FormatError::Corrupt("the last frame is cut short".to_string()))?;
+            values.push(bytes.chunks_exact(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())).collect());
+        }
+        Ok(Some(Frame { step: u32::from_le_bytes(step), time: f32::from_le_bytes(time), values }))
+    }
+}
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Result<Frame, FormatError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], FormatError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes).map_err(|_| FormatError::Corrupt("the header is cut short".to_string()))?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, FormatError> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+fn read_f32(reader: &mut impl Read) -> Result<f32, FormatError> {
+    Ok(f32::from_le_bytes(read_array(reader)?))
+}
+
+fn read_string(reader: &mut impl Read, length: usize) -> Result<String, FormatError> {
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(FormatError::Corrupt("the header is cut short".to_string()));
+    }
+    String::from_utf8(bytes).map_err(|_| FormatError::Corrupt("a string is not UTF-8".to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Field3Vec;
+
+    fn header(space_culling_factor: u32, origin: [usize; 3]) -> Header {
+        Header {
+            dimensions: [2, 3, 1],
+            origin,
+            spacing: [0.1, 0.2, 0.3],
+            frame_dt: 0.05,
+            e0: 1.0,
+            m0: 2.0,
+            b_time_offset: -0.005,
+            time_culling_factor: 5,
+            space_culling_factor,
+            components: vec![Component::Ez, Component::Bx],
+            manifest: "{\"constants\": {}}".to_string()
+        }
+    }
+
+    // Every node's Ez and Bx tell where it is
+    fn latice() -> Latice {
+        let mut latice = Latice::new([6, 6, 6]);
+        for z in 0..6 {
+            for y in 0..6 {
+                for x in 0..6 {
+                    let node = &mut latice[(x, y, z)];
+                    node.e = Field3Vec { components: [0.0, 0.0, (x + 10 * y + 100 * z) as f32] };
+                    node.b = Field3Vec { components: [-((x + 10 * y + 100 * z) as f32), 0.0, 0.0] };
+                }
+            }
+        }
+        latice
+    }
+
+    fn written(header: Header, frames: u32) -> Vec<u8> {
+        let latice = latice();
+        let mut writer = FrameWriter::new(Vec::new(), header).unwrap();
+        for frame in 0..frames {
+            writer.write_frame(5 * frame, 0.25 * frame as f32, &latice).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = written(header(2, [1, 1, 1]), 3);
+        let mut reader = FrameReader::new(file.as_slice()).unwrap();
+        let read = reader.header();
+        let expected = header(2, [1, 1, 1]);
+        assert_eq!(read.dimensions, expected.dimensions);
+        assert_eq!(read.origin, expected.origin);
+        assert_eq!(read.spacing, expected.spacing);
+        assert_eq!((read.frame_dt, read.e0, read.m0), (expected.frame_dt, expected.e0, expected.m0));
+        assert_eq!(read.b_time_offset, expected.b_time_offset);
+        assert_eq!((read.time_culling_factor, read.space_culling_factor), (5, 2));
+        assert_eq!(read.components, expected.components);
+        assert_eq!(read.manifest, expected.manifest);
+        let header = reader.header().clone();
+        let frames: Vec<Frame> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(frames.len(), 3);
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!((frame.step, frame.time), (5 * i as u32, 0.25 * i as f32));
+            assert_eq!(frame.b_time(&header), 0.25 * i as f32 - 0.005);
+            assert_eq!(frame.values.len(), 2);
+            // Every other node from (1, 1, 1)
+            assert_eq!(frame.value(&header, 0, (1, 2, 0)), 3.0 + 50.0 + 100.0);
+            assert_eq!(frame.value(&header, 1, (0, 1, 0)), -(1.0 + 30.0 + 100.0));
+        }
+    }
+
+    #[test]
+    fn monitored_region() {
+        let file = written(header(1, [4, 2, 5]), 1);
+        let mut reader = FrameReader::new(file.as_slice()).unwrap();
+        let header = reader.header().clone();
+        let frame = reader.read_frame().unwrap().unwrap();
+        assert_eq!(frame.values[0], vec![524.0, 525.0, 534.0, 535.0, 544.0, 545.0]);
+        assert_eq!(frame.value(&header, 1, (1, 2, 0)), -545.0);
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn wrong_magic() {
+        let mut file = written(header(1, [0; 3]), 1);
+        file[0] = b'X';
+        assert!(matches!(FrameReader::new(file.as_slice()), Err(FormatError::NotAFieldFile)));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut file = written(header(1, [0; 3]), 1);
+        file[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(FrameReader::new(file.as_slice()), Err(FormatError::UnsupportedVersion(version)) if version == VERSION + 1));
+    }
+
+    #[test]
+    fn cut_off_header() {
+        let file = written(header(1, [0; 3]), 0);
+        for length in [12, 40, file.len() - 1] {
+            match FrameReader::new(&file[..length]) {
+                Err(FormatError::Corrupt(message)) => assert_eq!(message, "the header is cut short"),
+                _ => panic!("a header cut to {length} bytes was read")
+            }
+        }
+    }
+
+    #[test]
+    fn cut_off_frame() {
+        let file = written(header(1, [0; 3]), 2);
+        let header_len = written(header(1, [0; 3]), 0).len();
+        let frame_len = (file.len() - header_len) / 2;
+        // Within the second frame's time, then its values
+        for length in [header_len + frame_len + 6, file.len() - 1] {
+            let mut reader = FrameReader::new(&file[..length]).unwrap();
+            assert!(reader.read_frame().unwrap().is_some());
+            match reader.read_frame() {
+                Err(FormatError::Corrupt(message)) => assert_eq!(message, "the last frame is cut short"),
+                _ => panic!("a frame cut to {length} bytes was read")
+            }
+        }
+    }
+}
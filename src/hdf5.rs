@@ -0,0 +1,346 @@
+use std::io::{self, Write};
+
+
+/*
+ * Just enough of HDF5 to store a few float datasets in the root group, in the original layout
+ * every version of the library reads:
+ *   superblock version 0, its root symbol table entry caching the root group's B-tree and heap
+ *   root group object header (version 1) with a symbol table message
+ *   local heap holding the dataset names, then a free block
+ *   group B-tree (version 1) leaf with a single symbol table node listing the datasets by name,
+ *   the superblock's group leaf node K making the node as large as the datasets need
+ *   one object header per dataset: dataspace, 32 bit little endian float datatype, fill value
+ *   and contiguous layout messages
+ *   the datasets' raw data, one after the other
+ * Offsets and lengths are 8 bytes, every number little endian.
+ */
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const UNDEFINED: u64 = u64::MAX;
+// Symbol table node and group B-tree capacities, half their number of entries. Symbol table nodes
+// are made larger when there are more datasets than the library's default fits.
+const DEFAULT_GROUP_LEAF_K: usize = 4;
+const GROUP_INTERNAL_K: usize = 16;
+
+const SUPERBLOCK_SIZE: usize = 96;
+const OBJECT_HEADER_PREFIX_SIZE: usize = 16;
+const MESSAGE_HEADER_SIZE: usize = 8;
+const LOCAL_HEAP_HEADER_SIZE: usize = 32;
+// Free block at the end of the heap, for readers that expect one
+const HEAP_FREE_BLOCK_SIZE: usize = 16;
+const B_TREE_SIZE: usize = 24 + 2 * GROUP_INTERNAL_K * 8 + (2 * GROUP_INTERNAL_K + 1) * 8;
+const SYMBOL_TABLE_ENTRY_SIZE: usize = 40;
+
+pub struct Dataset<'a> {
+    pub name: &'a str,
+    // Slowest varying first, like C arrays
+    pub shape: Vec<usize>,
+    pub values: &'a [f32]
+}
+
+// Writes a whole file holding `datasets` in its root group
+pub fn write_file(writer: &mut impl Write, datasets: &[Dataset]) -> io::Result<()> {
+    let group_leaf_k = DEFAULT_GROUP_LEAF_K.max(datasets.len().div_ceil(2));
+    if group_leaf_k > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} datasets are too many for one HDF5 group", datasets.len())));
+    }
+    let symbol_table_node_size = 8 + 2 * group_leaf_k * SYMBOL_TABLE_ENTRY_SIZE;
+    for dataset in datasets {
+        if dataset.shape.iter().product::<usize>() != dataset.values.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} doesn't fill its shape", dataset.name)));
+        }
+    }
+    // Symbol table nodes list their entries in name order
+    let mut datasets: Vec<&Dataset> = datasets.iter().collect();
+    datasets.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+
+    // Heap: the root's empty name, then the dataset names, each null terminated and padded to 8 bytes
+    let mut heap = vec![0; 8];
+    let mut name_offsets = Vec::new();
+    for dataset in &datasets {
+        name_offsets.push(heap.len() as u64);
+        heap.extend_from_slice(dataset.name.as_bytes());
+        heap.push(0);
+        heap.resize(heap.len().next_multiple_of(8), 0);
+    }
+    let free_block_offset = heap.len() as u64;
+    heap.extend_from_slice(&1u64.to_le_bytes());
+    heap.extend_from_slice(&(HEAP_FREE_BLOCK_SIZE as u64).to_le_bytes());
+
+    let root_header_address = SUPERBLOCK_SIZE;
+    let root_header_size = OBJECT_HEADER_PREFIX_SIZE + MESSAGE_HEADER_SIZE + 16;
+    let heap_address = root_header_address + root_header_size;
+    let heap_data_address = heap_address + LOCAL_HEAP_HEADER_SIZE;
+    let b_tree_address = heap_data_address + heap.len();
+    let symbol_table_node_address = b_tree_address + B_TREE_SIZE;
+    let mut dataset_header_address = symbol_table_node_address + symbol_table_node_size;
+    let mut data_address = dataset_header_address + datasets.iter().map(|dataset| dataset_header_size(dataset.shape.len())).sum::<usize>();
+    let mut object_headers = Vec::new();
+    let mut data_addresses = Vec::new();
+    for dataset in &datasets {
+        object_headers.push(dataset_header_address);
+        data_addresses.push(data_address);
+        dataset_header_address += dataset_header_size(dataset.shape.len());
+        data_address += 4 * dataset.values.len();
+    }
+    let end_of_file = data_address;
+
+    let mut out = Vec::with_capacity(data_addresses.first().copied().unwrap_or(end_of_file));
+    // Superblock
+    out.extend_from_slice(SIGNATURE);
+    // Superblock, free space, root entry and shared message versions, offset and length sizes
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 8, 8, 0]);
+    out.extend_from_slice(&(group_leaf_k as u16).to_le_bytes());
+    out.extend_from_slice(&(GROUP_INTERNAL_K as u16).to_le_bytes());
+    // File consistency flags
+    out.extend_from_slice(&0u32.to_le_bytes());
+    // Base address, free space info, end of file, driver information
+    for address in [0, UNDEFINED, end_of_file as u64, UNDEFINED] {
+        out.extend_from_slice(&address.to_le_bytes());
+    }
+    symbol_table_entry(&mut out, 0, root_header_address as u64, Some((b_tree_address as u64, heap_address as u64)));
+
+    // Root group object header
+    object_header_prefix(&mut out, 1, root_header_size - OBJECT_HEADER_PREFIX_SIZE);
+    message(&mut out, 0x0011, &[(b_tree_address as u64).to_le_bytes(), (heap_address as u64).to_le_bytes()].concat());
+
+    // Local heap
+    out.extend_from_slice(b"HEAP");
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(&(heap.len() as u64).to_le_bytes());
+    out.extend_from_slice(&free_block_offset.to_le_bytes());
+    out.extend_from_slice(&(heap_data_address as u64).to_le_bytes());
+    out.extend_from_slice(&heap);
+
+    // Group B-tree leaf pointing at the symbol table node, keyed by the names around it
+    let b_tree_start = out.len();
+    out.extend_from_slice(b"TREE");
+    // Group node, leaf, one child
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&UNDEFINED.to_le_bytes());
+    out.extend_from_slice(&UNDEFINED.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&(symbol_table_node_address as u64).to_le_bytes());
+    out.extend_from_slice(&name_offsets.last().copied().unwrap_or(0).to_le_bytes());
+    out.resize(b_tree_start + B_TREE_SIZE, 0);
+
+    // Symbol table node
+    let node_start = out.len();
+    out.extend_from_slice(b"SNOD");
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(datasets.len() as u16).to_le_bytes());
+    for (name_offset, header_address) in name_offsets.iter().zip(&object_headers) {
+        symbol_table_entry(&mut out, *name_offset, *header_address as u64, None);
+    }
+    out.resize(node_start + symbol_table_node_size, 0);
+
+    // Dataset object headers
+    for (dataset, address) in datasets.iter().zip(&data_addresses) {
+        let rank = dataset.shape.len();
+        object_header_prefix(&mut out, 4, dataset_header_size(rank) - OBJECT_HEADER_PREFIX_SIZE);
+        // Dataspace, version 1, no maximum dimensions
+        let mut dataspace = vec![1, rank as u8, 0, 0, 0, 0, 0, 0];
+        for &n in &dataset.shape {
+            dataspace.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        message(&mut out, 0x0001, &dataspace);
+        // Datatype, version 1 floating point: little endian, implied leading mantissa bit, sign at bit
+        // 31, 4 bytes, 32 bits from bit 0, 8 bit exponent at 23, 23 bit mantissa at 0, bias 127
+        let mut datatype = vec![0x11, 0x20, 31, 0];
+        datatype.extend_from_slice(&4u32.to_le_bytes());
+        datatype.extend_from_slice(&0u16.to_le_bytes());
+        datatype.extend_from_slice(&32u16.to_le_bytes());
+        datatype.extend_from_slice(&[23, 8, 0, 23]);
+        datatype.extend_from_slice(&127u32.to_le_bytes());
+        message(&mut out, 0x0003, &datatype);
+        // Fill value, version 2: allocated late, written if set, undefined
+        message(&mut out, 0x0005, &[2, 2, 2, 0]);
+        // Layout, version 3: contiguous at `address`
+        let mut layout = vec![3, 1];
+        layout.extend_from_slice(&(*address as u64).to_le_bytes());
+        layout.extend_from_slice(&(4 * dataset.values.len() as u64).to_le_bytes());
+        message(&mut out, 0x0008, &layout);
+    }
+    writer.write_all(&out)?;
+
+    let mut buffer = Vec::new();
+    for dataset in &datasets {
+        for chunk in dataset.values.chunks(4096) {
+            buffer.clear();
+            for value in chunk {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            writer.write_all(&buffer)?;
+        }
+    }
+    Ok(())
+}
+
+fn padded(size: usize) -> usize {
+    size.next_multiple_of(8)
+}
+
+fn dataset_header_size(rank: usize) -> usize {
+    OBJECT_HEADER_PREFIX_SIZE
+        + MESSAGE_HEADER_SIZE + padded(8 + 8 * rank)
+        + MESSAGE_HEADER_SIZE + padded(20)
+        + MESSAGE_HEADER_SIZE + padded(4)
+        + MESSAGE_HEADER_SIZE + padded(18)
+}
+
+// Version 1 object header prefix, padded so that the messages start 8 byte aligned
+fn object_header_prefix(out: &mut Vec<u8>, messages: u16, size: usize) {
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&messages.to_le_bytes());
+    // Reference count
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&(size as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+}
+
+fn message(out: &mut Vec<u8>, message_type: u16, data: &[u8]) {
+    out.extend_from_slice(&message_type.to_le_bytes());
+    out.extend_from_slice(&(padded(data.len()) as u16).to_le_bytes());
+    // Flags and reserved bytes
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(data);
+    out.resize(out.len() + padded(data.len()) - data.len(), 0);
+}
+
+// `group` caches the B-tree and heap addresses of a group's symbol table
+fn symbol_table_entry(out: &mut Vec<u8>, name_offset: u64, header_address: u64, group: Option<(u64, u64)>) {
+    out.extend_from_slice(&name_offset.to_le_bytes());
+    out.extend_from_slice(&header_address.to_le_bytes());
+    match group {
+        Some((b_tree_address, heap_address)) => {
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&b_tree_address.to_le_bytes());
+            out.extend_from_slice(&heap_address.to_le_bytes());
+        }
+        None => out.extend_from_slice(&[0; 24])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(file: &[u8], at: usize) -> usize {
+        u16::from_le_bytes(file[at..at + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(file: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(file[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn u64_at(file: &[u8], at: usize) -> usize {
+        u64::from_le_bytes(file[at..at + 8].try_into().unwrap()) as usize
+    }
+
+    fn name_at(file: &[u8], at: usize) -> String {
+        let end = file[at..].iter().position(|&byte| byte == 0).unwrap();
+        String::from_utf8(file[at..at + end].to_vec()).unwrap()
+    }
+
+    // Follows the addresses a reader would from the superblock to every dataset of the root group,
+    // returning their names, shapes and values
+    fn read_file(file: &[u8]) -> Vec<(String, Vec<usize>, Vec<f32>)> {
+        assert_eq!(&file[..8], SIGNATURE);
+        assert_eq!(&file[8..16], &[0, 0, 0, 0, 0, 8, 8, 0]);
+        assert_eq!(u64_at(file, 40), file.len(), "end of file address");
+        // Root symbol table entry, its scratch pad caching the group's B-tree and heap
+        let root = 56;
+        assert_eq!(u32_at(file, root + 16), 1);
+        let (b_tree_address, heap_address) = (u64_at(file, root + 24), u64_at(file, root + 32));
+        let root_header = u64_at(file, root + 8);
+        assert_eq!(file[root_header], 1, "object header version");
+        assert_eq!(u16_at(file, root_header + 16), 0x0011, "symbol table message");
+        assert_eq!((u64_at(file, root_header + 24), u64_at(file, root_header + 32)), (b_tree_address, heap_address));
+
+        assert_eq!(&file[heap_address..heap_address + 4], b"HEAP");
+        let heap_data = u64_at(file, heap_address + 24);
+        assert_eq!(&file[b_tree_address..b_tree_address + 4], b"TREE");
+        assert_eq!((file[b_tree_address + 4], file[b_tree_address + 5]), (0, 0), "group leaf");
+        assert_eq!(u16_at(file, b_tree_address + 6), 1);
+        let node = u64_at(file, b_tree_address + 32);
+        assert_eq!(&file[node..node + 4], b"SNOD");
+        assert!(u16_at(file, node + 6) <= 2 * u16_at(file, 16), "symbol table node overflowing its group leaf node K");
+
+        let mut datasets = Vec::new();
+        for entry in 0..u16_at(file, node + 6) {
+            let entry = node + 8 + entry * SYMBOL_TABLE_ENTRY_SIZE;
+            let name = name_at(file, heap_data + u64_at(file, entry));
+            let header = u64_at(file, entry + 8);
+            assert_eq!(file[header], 1, "object header version");
+            let (mut shape, mut data) = (Vec::new(), None);
+            let mut message = header + OBJECT_HEADER_PREFIX_SIZE;
+            for _ in 0..u16_at(file, header + 2) {
+                let body = message + MESSAGE_HEADER_SIZE;
+                match u16_at(file, message) {
+                    0x0001 => shape = (0..file[body + 1] as usize).map(|axis| u64_at(file, body + 8 + 8 * axis)).collect(),
+                    0x0003 => {
+                        assert_eq!(file[body] & 0x0f, 1, "floating point class");
+                        assert_eq!(u32_at(file, body + 4), 4, "float size");
+                    }
+                    0x0008 => {
+                        assert_eq!((file[body], file[body + 1]), (3, 1), "contiguous layout");
+                        data = Some((u64_at(file, body + 2), u64_at(file, body + 10)));
+                    }
+                    _ => {}
+                }
+                message = body + u16_at(file, message + 2);
+            }
+            let (address, size) = data.unwrap();
+            let values = file[address..address + size].chunks(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect();
+            datasets.push((name, shape, values));
+        }
+        datasets
+    }
+
+    #[test]
+    fn datasets_read_back() {
+        let e: Vec<f32> = (0..24).map(|i| i as f32 * 0.5).collect();
+        let bz = [1.0, -2.0, 3.5, f32::MIN_POSITIVE];
+        let mut file = Vec::new();
+        write_file(&mut file, &[
+            Dataset { name: "E", shape: vec![2, 2, 2, 3], values: &e },
+            Dataset { name: "Bz", shape: vec![1, 2, 2], values: &bz }
+        ]).unwrap();
+        // In name order
+        assert_eq!(read_file(&file), vec![
+            ("Bz".to_string(), vec![1, 2, 2], bz.to_vec()),
+            ("E".to_string(), vec![2, 2, 2, 3], e)
+        ]);
+    }
+
+    #[test]
+    fn empty_file_reads_back() {
+        let mut file = Vec::new();
+        write_file(&mut file, &[]).unwrap();
+        assert!(read_file(&file).is_empty());
+    }
+
+    // More than the 8 entries of a default symbol table node
+    #[test]
+    fn many_datasets_read_back() {
+        let names: Vec<String> = (0..21).map(|i| format!("d{i:02}")).collect();
+        let values: Vec<[f32; 2]> = (0..21).map(|i| [i as f32, -(i as f32)]).collect();
+        let datasets: Vec<Dataset> = names.iter().zip(&values).map(|(name, values)| Dataset { name, shape: vec![2], values }).collect();
+        let mut file = Vec::new();
+        write_file(&mut file, &datasets).unwrap();
+        assert_eq!(u16_at(&file, 16), 11, "group leaf node K");
+        let read = read_file(&file);
+        assert_eq!(read.len(), 21);
+        for ((name, shape, read), (expected_name, expected)) in read.iter().zip(names.iter().zip(&values)) {
+            assert_eq!((name, shape, &read[..]), (expected_name, &vec![2], &expected[..]));
+        }
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_store() {
+        let error = write_file(&mut Vec::new(), &[Dataset { name: "Ex", shape: vec![2, 2], values: &[0.0; 3] }]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{fmt, ops};
+pub use simulation::{Simulation, SimulationBuilder};
+pub use source::{CurrentObject, SourceContext};
+
+pub mod antenna;
+pub mod boundary;
+pub mod dispersion;
+pub mod format;
+pub mod hdf5;
+pub mod manifest;
+pub mod material;
+pub mod output;
+pub mod plane_wave;
+pub mod probe;
+pub mod simulation;
+pub mod solver;
+pub mod source;
+pub mod vtk;
+pub mod waveform;
+pub mod xdmf;
+
+
+
+
+#[derive(Clone, Copy, Default)]
+pub struct SpaceData {
+    pub b: Field3Vec,
+    pub e: Field3Vec,
+    //neighbors: Neighbors<'a>
+    // Source driving the node, 0 for none
+    pub object_index: usize,
+    pub material_index: usize
+}
+impl ops::Add<SpaceData> for SpaceData {
+    type Output = SpaceData;
+
+    fn add(self, rhs: SpaceData) -> Self::Output {
+        Self { 
+            e: self.e + rhs.e,
+            b: self.b + rhs.b,
+            ..Self::default()
+        }
+    }
+}
+impl ops::Sub<SpaceData> for SpaceData {
+    type Output = SpaceData;
+
+    fn sub(self, rhs: SpaceData) -> Self::Output {
+        Self {
+            e: self.e - rhs.e,
+            b: self.b - rhs.b,
+            ..Self::default()
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Default)]
+pub struct Field3Vec {
+    pub components: [f32; 3]
+}
+impl ops::Add<Field3Vec> for Field3Vec {
+    type Output = Field3Vec;
+
+    fn add(self, rhs: Field3Vec) -> Self::Output {
+        Self { components: [
+                self.components[0] + rhs.components[0],
+                self.components[1] + rhs.components[1],
+                self.components[2] + rhs.components[2],
+        ] }
+    }
+}
+impl ops::Add<&Field3Vec> for &Field3Vec {
+    type Output = Field3Vec;
+
+    fn add(self, rhs: &Field3Vec) -> Self::Output {
+        Field3Vec { components: [
+                self.components[0] + rhs.components[0],
+                self.components[1] + rhs.components[1],
+                self.components[2] + rhs.components[2],
+        ] }
+    }
+}
+impl ops::Sub<Field3Vec> for Field3Vec {
+    type Output = Field3Vec;
+
+    fn sub(self, rhs: Field3Vec) -> Self::Output {
+        Field3Vec { components: [
+                self.components[0] - rhs.components[0],
+                self.components[1] - rhs.components[1],
+                self.components[2] - rhs.components[2],
+        ] }
+    }
+}
+impl ops::Sub<&Field3Vec> for &Field3Vec {
+    type Output = Field3Vec;
+
+    fn sub(self, rhs: &Field3Vec) -> Self::Output {
+        Field3Vec { components: [
+                self.components[0] - rhs.components[0],
+                self.components[1] - rhs.components[1],
+                self.components[2] - rhs.components[2],
+        ] }
+    }
+}
+
+
+
+impl ops::Mul<f32> for Field3Vec {
+    type Output = Field3Vec;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self { components: [
+                self.components[0] * rhs,
+                self.components[1] * rhs,
+                self.components[2] * rhs,
+        ] }
+    }
+}
+impl ops::Mul<f32> for &Field3Vec {
+    type Output = Field3Vec;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Field3Vec { components: [
+                self.components[0] * rhs,
+                self.components[1] * rhs,
+                self.components[2] * rhs,
+        ] }
+    }
+}
+impl ops::Div<f32> for Field3Vec {
+    type Output = Field3Vec;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Self { components: [
+                self.components[0] / rhs,
+                self.components[1] / rhs,
+                self.components[2] / rhs,
+        ] }
+    }
+}
+impl ops::Div<f32> for &Field3Vec {
+    type Output = Field3Vec;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Field3Vec { components: [
+                self.components[0] / rhs,
+                self.components[1] / rhs,
+                self.components[2] / rhs,
+        ] }
+    }
+}
+impl fmt::Display for Field3Vec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}, {}, {}>", self.components[0], self.components[1], self.components[2])
+    }
+}
+
+impl ops::Mul<Field3Vec> for f32 {
+    type Output = Field3Vec;
+
+    fn mul(self, rhs: Field3Vec) -> Self::Output {
+        rhs.mul(self)
+    }
+}
+
+impl Field3Vec {
+    pub fn dot(self, rhs: Self) -> f32 {
+        self.components[0] * rhs.components[0] +
+        self.components[1] * rhs.components[1] +
+        self.components[2] * rhs.components[2]
+    }
+
+    pub fn cross(self, rhs: Self) -> Self {
+        Self { components: [
+            self.components[1] * rhs.components[2] - self.components[2] * rhs.components[1],
+            self.components[2] * rhs.components[0] - self.components[0] * rhs.components[2],
+            self.components[0] * rhs.components[1] - self.components[1] * rhs.components[0]
+        ] }
+    }
+
+    pub fn norm(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Latice {
+    pub dimensions: [usize; 3],
+    pub cells: Vec<SpaceData>
+}
+impl Latice {
+    pub fn new(dimensions: [usize; 3]) -> Self {
+        Self {
+            dimensions,
+            cells: vec![SpaceData::default(); dimensions[0] * dimensions[1] * dimensions[2]]
+        }
+    }
+
+    pub fn index(&self, (x, y, z): (usize, usize, usize)) -> usize {
+        (z * self.dimensions[1] + y) * self.dimensions[0] + x
+    }
+}
+impl ops::Index<(usize, usize, usize)> for Latice {
+    type Output = SpaceData;
+
+    fn index(&self, position: (usize, usize, usize)) -> &Self::Output {
+        &self.cells[Latice::index(self, position)]
+    }
+}
+impl ops::IndexMut<(usize, usize, usize)> for Latice {
+    fn index_mut(&mut self, position: (usize, usize, usize)) -> &mut Self::Output {
+        let i = Latice::index(self, position);
+        &mut self.cells[i]
+    }
+}
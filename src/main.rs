@@ -1,221 +1,13 @@
-use std::{array, fmt, fs::File, io::{self, Write}, ops, process::ExitCode};
-use json::Value;
-use serde_json as json;
+use std::{fs::File, io::{self, Write}, path::{Path, PathBuf}, process::ExitCode};
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use maximillion::Simulation;
+use maximillion::manifest::{Manifest, Object, Output};
+use maximillion::output::{self, Outputs};
+use maximillion::probe::ProbeRecorder;
+use maximillion::solver::Solver;
 
 
-enum BoundaryCondition {
-    Clip,
-    Fit
-}
-
-#[derive(Clone)]
-struct SpaceData {
-    b: Field3Vec,
-    e: Field3Vec,
-    //neighbors: Neighbors<'a>
-    object_index: usize
-}
-impl Default for SpaceData {
-    fn default() -> Self {
-        Self {
-            e: Field3Vec::default(),
-            b: Field3Vec::default(),
-            object_index: 0
-        }
-    }
-}
-impl ops::Add<SpaceData> for SpaceData {
-    type Output = SpaceData;
-
-    fn add(self, rhs: SpaceData) -> Self::Output {
-        Self { 
-            e: self.e + rhs.e,
-            b: self.b + rhs.b,
-            ..Self::default()
-        }
-    }
-}
-impl ops::Sub<SpaceData> for SpaceData {
-    type Output = SpaceData;
-
-    fn sub(self, rhs: SpaceData) -> Self::Output {
-        Self {
-            e: self.e - rhs.e,
-            b: self.b - rhs.b,
-            ..Self::default()
-        }
-    }
-}
-
-
-#[derive(Clone)]
-struct Field3Vec {
-    components: Vec<f32>
-}
-impl Default for Field3Vec {
-    fn default() -> Self {
-        Self {components: vec![0.0, 0.0, 0.0]}
-    }
-}
-impl ops::Add<Field3Vec> for Field3Vec {
-    type Output = Field3Vec;
-
-    fn add(self, rhs: Field3Vec) -> Self::Output {
-        Self { components: vec![
-                self.components[0] + rhs.components[0],
-                self.components[1] + rhs.components[1],
-                self.components[2] + rhs.components[2],
-        ] }
-    }
-}
-impl ops::Add<&Field3Vec> for &Field3Vec {
-    type Output = Field3Vec;
-
-    fn add(self, rhs: &Field3Vec) -> Self::Output {
-        Field3Vec { components: vec![
-                self.components[0] + rhs.components[0],
-                self.components[1] + rhs.components[1],
-                self.components[2] + rhs.components[2],
-        ] }
-    }
-}
-impl ops::Sub<Field3Vec> for Field3Vec {
-    type Output = Field3Vec;
-
-    fn sub(self, rhs: Field3Vec) -> Self::Output {
-        Field3Vec { components: vec![
-                self.components[0] - rhs.components[0],
-                self.components[1] - rhs.components[1],
-                self.components[2] - rhs.components[2],
-        ] }
-    }
-}
-impl ops::Sub<&Field3Vec> for &Field3Vec {
-    type Output = Field3Vec;
-
-    fn sub(self, rhs: &Field3Vec) -> Self::Output {
-        Field3Vec { components: vec![
-                self.components[0] - rhs.components[0],
-                self.components[1] - rhs.components[1],
-                self.components[2] - rhs.components[2],
-        ] }
-    }
-}
-
-
-
-impl ops::Mul<f32> for Field3Vec {
-    type Output = Field3Vec;
-
-    fn mul(self, rhs: f32) -> Self::Output {
-        Self { components: vec![
-                self.components[0] * rhs,
-                self.components[1] * rhs,
-                self.components[2] * rhs,
-        ] }
-    }
-}
-impl ops::Mul<f32> for &Field3Vec {
-    type Output = Field3Vec;
-
-    fn mul(self, rhs: f32) -> Self::Output {
-        Field3Vec { components: vec![
-                self.components[0] * rhs,
-                self.components[1] * rhs,
-                self.components[2] * rhs,
-        ] }
-    }
-}
-impl ops::Div<f32> for Field3Vec {
-    type Output = Field3Vec;
-
-    fn div(self, rhs: f32) -> Self::Output {
-        Self { components: vec![
-                self.components[0] / rhs,
-                self.components[1] / rhs,
-                self.components[2] / rhs,
-        ] }
-    }
-}
-impl ops::Div<f32> for &Field3Vec {
-    type Output = Field3Vec;
-
-    fn div(self, rhs: f32) -> Self::Output {
-        Field3Vec { components: vec![
-                self.components[0] / rhs,
-                self.components[1] / rhs,
-                self.components[2] / rhs,
-        ] }
-    }
-}
-impl fmt::Display for Field3Vec {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<{}, {}, {}>", self.components[0], self.components[1], self.components[2])
-    }
-}
-
-impl ops::Mul<Field3Vec> for f32 {
-    type Output = Field3Vec;
-
-    fn mul(self, rhs: Field3Vec) -> Self::Output {
-        rhs.mul(self)
-    }
-}
-
-trait CurrentObject {
-    fn currrent_density(&self, t: f32) -> Field3Vec;
-}
-
-
-#[derive(Clone)]
-struct Wire {
-    angular_frequency: f32,
-    amplitude: f32,
-    direction: Field3Vec,
-}
-impl CurrentObject for Wire {
-    fn currrent_density(&self, t: f32) -> Field3Vec {
-        self.amplitude * (t * self.angular_frequency).sin() * self.direction.clone()
-    }
-}
-
-
-#[derive(Clone)]
-struct Vaccum;
-impl CurrentObject for Vaccum {
-    fn currrent_density(&self, t: f32) -> Field3Vec {
-        Field3Vec::default()
-    }
-}
-
-/*impl Field3Vec {
-    fn dot(self, rhs: Self) -> f32 {
-        self.components[0] * rhs.components[0] +
-        self.components[1] * rhs.components[1] +
-        self.components[2] * rhs.components[2]
-    }
-
-    fn cross(self, rhs: Self) -> Self {
-        Self { components: vec![
-            self.components[1] * self.components[2] - self.components[2] * self.components[1],
-            self.components[2] * self.components[0] - self.components[0] * self.components[2],
-            self.components[0] * self.components[1] - self.components[1] * self.components[0]
-        ] }
-    }
-}*/
-
-
-fn generate_blank_latice() -> LaticeType {
-    Box::new(array::from_fn(
-        |_| Box::new(array::from_fn(
-            |_| Box::new(array::from_fn(
-                |_| SpaceData::default()
-            ))
-        ))
-    ))
-}
-
-fn update_progress_bar(val: u32, max: u32, message:&str) -> () {
+fn update_progress_bar(val: u32, max: u32, message:&str) {
     eprint!(
         "\r{} ({}/{}) ({:.1}%) [{: <10}]",
         message,
@@ -228,406 +20,230 @@ fn update_progress_bar(val: u32, max: u32, message:&str) -> () {
 
 
 
-
-const LATICE_DENSITY: u32 = 30;
-const SIMULATION_SIDE_LENGTH: u32 = 1;
-
-//type LaticeType = [[[SpaceData; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize]; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize]; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize];
-type LaticeType = Box<[Box<[Box<[SpaceData; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize]>; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize]>; LATICE_DENSITY as usize*SIMULATION_SIDE_LENGTH as usize]>;
-
-fn main() -> ExitCode {
-    eprint!("Manifest filename? ");
-    let mut input = String::new();
-    let _ = io::stdin().read_line(&mut input);
-    let manifest_filename = &input[0..input.len()-2];
-
-    eprintln!("");
-    eprintln!("Simulating from \"{manifest_filename}\"...");
-    
-    //eprintln!("{manifest_filename}");
-    //let a = manifest_filename.len();
-    //eprintln!("{a}");
-    let json_file = File::open(manifest_filename);
-    if json_file.is_err() {
-        return ExitCode::FAILURE;
-    }
-    let json_data: Value = json::from_reader(json_file.unwrap()).unwrap();
+fn write_report(path: &Path, write: impl FnOnce(&mut io::BufWriter<File>) -> io::Result<()>) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()
+}
 
 
-    let permittivity: f32 = json_data["constants"]["e0"].as_f64().unwrap() as f32;//8.85e-12;
-    let permeability: f32 = json_data["constants"]["m0"].as_f64().unwrap() as f32;//PI*4e-7;
-    let e0: f32 = permittivity;
-    let m0: f32 = permeability;
-    let dt: f32 = json_data["constants"]["dt"].as_f64().unwrap() as f32;
-    let steps: u32 = json_data["constants"]["steps"].as_i64().unwrap() as u32;
-    let time_culling_factor:u32 = json_data["constants"]["time_culling_factor"].as_i64().unwrap() as u32;
-    let space_culling_factor:u32 = json_data["constants"]["space_culling_factor"].as_i64().unwrap() as u32;
-    let boundary_condition:BoundaryCondition;
-    if json_data["constants"]["boundary_condition"].as_str().unwrap().to_lowercase() == "clip" {
-        boundary_condition = BoundaryCondition::Clip;
-    } else if json_data["constants"]["boundary_condition"].as_str().unwrap().to_lowercase() == "fit" {
-        boundary_condition = BoundaryCondition::Fit;
-    } else {
-        return ExitCode::FAILURE;
-    }
-    
-    // Prepare pipeline to display program
-    let mut out_writer = io::BufWriter::new(io::stdout());
-    // Send constants for data reconstruction
-    let _ = out_writer.write_all(&(LATICE_DENSITY as f32 / space_culling_factor as f32).to_le_bytes());
-    let _ = out_writer.write_all(&(dt * time_culling_factor as f32).to_le_bytes());
-    // Prepare memory for field data
-    let mut current = generate_blank_latice();
-    let mut next = generate_blank_latice();
-    
-    let mut field_objects: Vec<Box<dyn CurrentObject>> = vec![Box::new(Vaccum{})];
 
-    let mut current_field_object_index:usize = 1;
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Legacy,
+    Binary,
+    // HDF5 snapshots described by an XDMF file, for ParaView and VisIt
+    Hdf5,
+    // VTK image data snapshots gathered by a .pvd collection
+    Vtk,
+    // Numbered legacy VTK files
+    VtkLegacy
+}
 
-    // Configure initial conditions
-    for object in json_data["objects"].as_array().unwrap() {
-        let mut field_object: Box<dyn CurrentObject>;
-        let object_type = object["type"].as_str().unwrap();
-        if object_type == "point" {
-            let e = object["E"].as_array().unwrap();
-            let e_x = e[0].as_f64().unwrap() as f32;
-            let e_y = e[1].as_f64().unwrap() as f32;
-            let e_z = e[2].as_f64().unwrap() as f32;
+#[derive(Parser)]
+#[command(version, about = "Electromagnetic field simulator, streams the simulated fields to the display")]
+struct Cli {
+    // Asked for on stdin when left out, so `simulate | python display.py` keeps working
+    /// Manifest describing the simulation
+    manifest: Option<String>,
+    /// Write the field data to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Override the manifest's number of steps
+    #[arg(long)]
+    steps: Option<u32>,
+    /// Override the manifest's time step
+    #[arg(long)]
+    dt: Option<f32>,
+    /// Don't print progress to stderr
+    #[arg(short, long)]
+    quiet: bool,
+    /// Format of the field data when the manifest lists no outputs: the display stream (the
+    /// default), a self-describing binary file, or snapshots for ParaView named after the output,
+    /// the XDMF file (hdf5) or .pvd collection (vtk) to write
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Check the manifest and exit without simulating
+    #[arg(long)]
+    validate_only: bool
+}
 
-            let b = object["B"].as_array().unwrap();
-            let b_x = b[0].as_f64().unwrap() as f32;
-            let b_y = b[1].as_f64().unwrap() as f32;
-            let b_z = b[2].as_f64().unwrap() as f32;
+// Exit code when the output can't be written, the manifest's own errors use 3 to 6
+const OUTPUT_ERROR_EXIT_CODE: u8 = 7;
 
-            current
-                [object["location"][2].as_i64().unwrap() as usize]
-                [object["location"][1].as_i64().unwrap() as usize]
-                [object["location"][0].as_i64().unwrap() as usize]
-            = SpaceData {
-                e: Field3Vec{ components:vec![
-                    e_x, e_y, e_z
-                ] },
-                b: Field3Vec{ components:vec![
-                    b_x, b_y, b_z
-                ] },
-                ..SpaceData::default()
-            };
-        } else if object_type == "plane" {
-            let axis = object["axis"].as_str().unwrap();
-            let location = object["location"].as_i64().unwrap() as usize;
-            let e = object["E"].as_array().unwrap();
-            let e_x = e[0].as_f64().unwrap() as f32;
-            let e_y = e[1].as_f64().unwrap() as f32;
-            let e_z = e[2].as_f64().unwrap() as f32;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let manifest_filename = match cli.manifest {
+        Some(manifest_filename) => manifest_filename,
+        None => {
+            eprint!("Manifest filename? ");
+            let mut input = String::new();
+            let _ = io::stdin().read_line(&mut input);
+            eprintln!();
+            input.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
 
-            let b = object["B"].as_array().unwrap();
-            let b_x = b[0].as_f64().unwrap() as f32;
-            let b_y = b[1].as_f64().unwrap() as f32;
-            let b_z = b[2].as_f64().unwrap() as f32;
+    if !cli.quiet && !cli.validate_only {
+        eprintln!("Simulating from \"{manifest_filename}\"...");
+    }
 
-            for i in 0..(LATICE_DENSITY * SIMULATION_SIDE_LENGTH) {
-                for j in 0..(LATICE_DENSITY * SIMULATION_SIDE_LENGTH) {
-                    if axis == "x" {
-                        current[i as usize][j as usize][location] = SpaceData {
-                            e: Field3Vec{ components:vec![e_x, e_y, e_z] },
-                            b: Field3Vec{ components:vec![b_x, b_y, b_z] },
-                            ..SpaceData::default()
-                        };
-                    } else if axis == "y" {
-                        current[i as usize][location][j as usize] = SpaceData {
-                            e: Field3Vec{ components:vec![e_x, e_y, e_z] },
-                            b: Field3Vec{ components:vec![b_x, b_y, b_z] },
-                            ..SpaceData::default()
-                        };
-                    } else if axis == "z" {
-                        current[location][j as usize][i as usize] = SpaceData {
-                            e: Field3Vec{ components:vec![e_x, e_y, e_z] },
-                            b: Field3Vec{ components:vec![b_x, b_y, b_z] },
-                            ..SpaceData::default()
-                        };
-                    }
-                    
-                }
-            }
-        } else if object_type == "wire" {
-            let axis = object["axis"].as_str().unwrap();
-            let location = object["location"].as_array().unwrap();
-            for i in 0..(LATICE_DENSITY * SIMULATION_SIDE_LENGTH) {
-                if axis == "x" {
-                    current[location[1].as_i64().unwrap() as usize][location[0].as_i64().unwrap() as usize][i as usize] = SpaceData {
-                        object_index: current_field_object_index,
-                        ..current[location[1].as_i64().unwrap() as usize][location[0].as_i64().unwrap() as usize][i as usize].clone()
-                    };
-                    field_object = Box::new(Wire{ 
-                        amplitude: object["amplitude"].as_f64().unwrap() as f32,
-                        angular_frequency: object["angular_frequency"].as_f64().unwrap() as f32,
-                        direction: Field3Vec{components: vec![1.0, 0.0, 0.0]}
-                    });
-                    field_objects.push(field_object);
-                } else if axis == "y" {
-                    current[location[1].as_i64().unwrap() as usize][i as usize][location[0].as_i64().unwrap() as usize] = SpaceData {
-                        object_index: current_field_object_index,
-                        ..current[location[1].as_i64().unwrap() as usize][i as usize][location[0].as_i64().unwrap() as usize].clone()
-                    };
-                    field_object = Box::new(Wire{ 
-                        amplitude: object["amplitude"].as_f64().unwrap() as f32,
-                        angular_frequency: object["angular_frequency"].as_f64().unwrap() as f32,
-                        direction: Field3Vec{components: vec![0.0, 1.0, 0.0]}
-                    });
-                    field_objects.push(field_object);
-                } else if axis == "z" {
-                    current[i as usize][location[1].as_i64().unwrap() as usize][location[0].as_i64().unwrap() as usize] = SpaceData {
-                        object_index: current_field_object_index,
-                        ..current[i as usize][location[1].as_i64().unwrap() as usize][location[0].as_i64().unwrap() as usize].clone()
-                    };
-                    field_object = Box::new(Wire{ 
-                        amplitude: object["amplitude"].as_f64().unwrap() as f32,
-                        angular_frequency: object["angular_frequency"].as_f64().unwrap() as f32,
-                        direction: Field3Vec{components: vec![0.0, 0.0, 1.0]}
-                    });
-                    field_objects.push(field_object);
-                }
-            }
-            current_field_object_index += 1;
+    let mut manifest = match Manifest::load(&manifest_filename) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            eprintln!("{manifest_filename}: {error}");
+            return ExitCode::from(error.exit_code());
+        }
+    };
+    if let Some(steps) = cli.steps {
+        manifest.constants.steps = steps;
+    }
+    if let Some(dt) = cli.dt {
+        manifest.constants.dt = dt;
+    }
+    if manifest.outputs.is_empty() {
+        let snapshot_file = || cli.output.clone().unwrap_or_else(|| {
+            Cli::command().error(ErrorKind::MissingRequiredArgument, "snapshot formats write files named after --output, which must be given").exit()
+        });
+        let (time_culling_factor, space_culling_factor, components) = (None, None, None);
+        manifest.outputs.push(match cli.format.unwrap_or(Format::Legacy) {
+            Format::Legacy => Output::Legacy { file: None, time_culling_factor, space_culling_factor },
+            Format::Binary => Output::Binary { file: None, time_culling_factor, space_culling_factor, components },
+            Format::Hdf5 => Output::Hdf5 { file: snapshot_file(), time_culling_factor, space_culling_factor, components },
+            Format::Vtk => Output::Vtk { file: snapshot_file(), time_culling_factor, space_culling_factor, components },
+            Format::VtkLegacy => Output::VtkLegacy { file: snapshot_file(), time_culling_factor, space_culling_factor, components }
+        });
+    } else if cli.format.is_some() {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--format can't be used with a manifest listing its outputs").exit();
+    } else if cli.output.is_some() && manifest.outputs.iter().all(|output| output.file().is_some()) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--output isn't used, every output the manifest lists names its own file").exit();
+    }
+    if let Err(error) = manifest.validate() {
+        eprintln!("{manifest_filename}: {error}");
+        return ExitCode::from(error.exit_code());
+    }
+    if cli.validate_only {
+        if !cli.quiet {
+            eprintln!("{manifest_filename}: manifest is valid");
         }
+        return ExitCode::SUCCESS;
     }
-    /*for i in 2..(LATICE_DENSITY * SIMULATION_SIDE_LENGTH -2) {
-        for j in 2..(LATICE_DENSITY * SIMULATION_SIDE_LENGTH -2) {
-            current[i as usize][j as usize][i as usize] = SpaceData {
-                e: Field3Vec{ components:vec![1.0, 0.0, 0.0] },
-                b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
-            };
+
+    let mut simulation = match Simulation::from_manifest(&manifest) {
+        Ok(simulation) => simulation,
+        Err(error) => {
+            eprintln!("{manifest_filename}: {error}");
+            return ExitCode::from(error.exit_code());
         }
-    }*/
-    /*current[15][15][15] = SpaceData {
-        e: Field3Vec{ components:vec![1.0, 0.0, 0.0] },
-        b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
-    };
-    current[17][15][15] = SpaceData {
-        e: Field3Vec{ components:vec![-1.0, 0.0, 0.0] },
-        b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
-    };
-    current[16][15][16] = SpaceData {
-        e: Field3Vec{ components:vec![0.0, 0.0, 1.0] },
-        b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
     };
-    current[16][15][14] = SpaceData {
-        e: Field3Vec{ components:vec![0.0, 0.0, -1.0] },
-        b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
-    };
-    current[15][15][15] = SpaceData {
-        e: Field3Vec{ components:vec![1.0, 0.0, 0.0] },
-        b: Field3Vec{ components:vec![0.0, 0.0, 0.0] },
-    };*/
-
-    //let mut totalDivergence:f32 = 0.0;
-
-    // Send initial conditions through pipeline
-    for (z, plane) in current.iter().enumerate() {
-        for (y, row) in plane.iter().enumerate() {
-            for (x, node) in row.iter().enumerate() {
-                let _ = out_writer.write_all(&node.e.components[0].to_le_bytes());
-                let _ = out_writer.write_all(&[0]);
-                let _ = out_writer.write_all(&node.e.components[1].to_le_bytes());
-                let _ = out_writer.write_all(&[0]);
-                let _ = out_writer.write_all(&node.e.components[2].to_le_bytes());
-                let _ = out_writer.write_all(&[1]);
-                let _ = out_writer.write_all(&node.b.components[0].to_le_bytes());
-                let _ = out_writer.write_all(&[0]);
-                let _ = out_writer.write_all(&node.b.components[1].to_le_bytes());
-                let _ = out_writer.write_all(&[0]);
-                let _ = out_writer.write_all(&node.b.components[2].to_le_bytes());
-                if z == current.len()-1 && y == plane.len()-1 && x == row.len()-1 {
-                    let _ = out_writer.write_all(&[5]);
-                } else if y == plane.len()-1 && x == row.len()-1 {
-                    let _ = out_writer.write_all(&[4]);
-                } else if x == row.len()-1 {
-                    let _ = out_writer.write_all(&[3]);
-                } else {
-                    let _ = out_writer.write_all(&[2]);
-                }
+    let dt: f32 = manifest.constants.dt;
+    let steps: u32 = manifest.constants.steps;
+    // Grid resolution (nodes per unit length) and physical extent, per axis
+    let latice_density = simulation.latice_density();
+    match manifest.constants.solver {
+        Solver::Yee => {
+            // Courant limit for the leapfrog scheme
+            let (e0, m0) = (manifest.constants.e0, manifest.constants.m0);
+            let courant = dt / (e0 * m0).sqrt() * latice_density.iter().map(|density| density * density).sum::<f32>().sqrt();
+            if courant > 1.0 {
+                eprintln!("Warning: Courant number {courant:.3} exceeds 1, the Yee solver will be unstable");
             }
         }
+        // Forward Euler amplifies every wave a little each step, whatever the time step
+        Solver::Collocated => eprintln!("Warning: the collocated solver is unstable and its fields grow without bound, set \"solver\": \"yee\" in the constants")
+    }
+    
+    // Prepare pipeline to display program, and whatever else the manifest lists
+    let mut outputs = Outputs::new();
+    for output in &manifest.outputs {
+        let stream = || -> io::Result<Box<dyn Write>> {
+            Ok(match &cli.output {
+                Some(path) => Box::new(output::create(path)?),
+                None => Box::new(io::stdout())
+            })
+        };
+        match output::open(output, &manifest, &manifest_filename, &simulation, stream) {
+            Ok(sink) => outputs.add(sink, output.time_culling_factor(&manifest.constants)),
+            Err(error) => {
+                eprintln!("Failed to write the simulation output: {error}");
+                return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+            }
+        };
+    }
+    for monitor in &manifest.monitors {
+        match output::open_monitor(monitor, &manifest, &manifest_filename, &simulation) {
+            Ok(sink) => outputs.add(sink, monitor.time_culling_factor(&manifest.constants)),
+            Err(error) => {
+                eprintln!("Failed to write the simulation output: {error}");
+                return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+            }
+        };
+    }
+    if let Some(probes) = &manifest.probes {
+        let points = probes.points.iter().map(|point| point.probe(latice_density)).collect();
+        match ProbeRecorder::new(&probes.file, probes.format, points) {
+            Ok(recorder) => outputs.add(Box::new(recorder), 1),
+            Err(error) => {
+                eprintln!("Failed to write the simulation output: {error}");
+                return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+            }
+        };
+    }
+    // Send initial conditions through pipeline
+    if let Err(error) = outputs.write(&simulation) {
+        eprintln!("Failed to write the simulation output: {error}");
+        return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
     }
 
     // Begin simulation (1 step is used for the initial conditions)
     let mut steps_left = steps-1;
-    let mut field_object_currents: Vec<Field3Vec> = vec![];
-    for field_object in &field_objects {
-        field_object_currents.push(field_object.currrent_density(0.0));
-    }
         
     while steps_left > 0 {
 
-        for (i, field_object) in field_objects.iter().enumerate() {
-            field_object_currents[i] = field_object.currrent_density((steps - steps_left) as f32 * dt);
-        }
-
-        for (z, plane) in current.iter().enumerate() {
-            for (y, row) in plane.iter().enumerate() {
-                for (x, node) in row.iter().enumerate() {
-
-                    let derivative_e_x: Field3Vec;
-                    let derivative_b_x: Field3Vec;
-                    if x == 0 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_x = (&current[z][y][x+1].e - &node.e) * LATICE_DENSITY as f32;
-                                derivative_b_x = (&current[z][y][x+1].b - &node.b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_x = (&current[z][y][x+1].e) * LATICE_DENSITY as f32 * 0.5;
-                                derivative_b_x = (&current[z][y][x+1].b) * LATICE_DENSITY as f32 * 0.5;
-                            }
-                        }
-                    } else if x == row.len() - 1 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_x = (&node.e - &current[z][y][x-1].e) * LATICE_DENSITY as f32;
-                                derivative_b_x = (&node.b - &current[z][y][x-1].b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_x = &current[z][y][x-1].e * LATICE_DENSITY as f32 * -0.5;
-                                derivative_b_x = &current[z][y][x-1].b * LATICE_DENSITY as f32 * -0.5;
-                            }
-                        }
-                    } else {
-                        derivative_e_x = (&current[z][y][x+1].e - &current[z][y][x-1].e) * LATICE_DENSITY as f32 * 0.5;
-                        derivative_b_x = (&current[z][y][x+1].b - &current[z][y][x-1].b) * LATICE_DENSITY as f32 * 0.5;
-                    }
-
-                    let derivative_e_y: Field3Vec;
-                    let derivative_b_y: Field3Vec;
-                    if y == 0 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_y = (&current[z][y+1][x].e - &node.e) * LATICE_DENSITY as f32;
-                                derivative_b_y = (&current[z][y+1][x].b - &node.b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_y = (&current[z][y+1][x].e) * LATICE_DENSITY as f32 * 0.5;
-                                derivative_b_y = (&current[z][y+1][x].b) * LATICE_DENSITY as f32 * 0.5;
-                            }
-                        }
-                    } else if y == row.len() - 1 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_y = (&node.e - &current[z][y-1][x].e) * LATICE_DENSITY as f32;
-                                derivative_b_y = (&node.b - &current[z][y-1][x].b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_y = &current[z][y-1][x].e * LATICE_DENSITY as f32 * -0.5;
-                                derivative_b_y = &current[z][y-1][x].b * LATICE_DENSITY as f32 * -0.5;
-                            }
-                        }
-                    } else {
-                        derivative_e_y = (&current[z][y+1][x].e - &current[z][y-1][x].e) * LATICE_DENSITY as f32 * 0.5;
-                        derivative_b_y = (&current[z][y+1][x].b - &current[z][y-1][x].b) * LATICE_DENSITY as f32 * 0.5;
-                    }
-
-                    let derivative_e_z: Field3Vec;
-                    let derivative_b_z: Field3Vec;
-                    if z == 0 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_z = (&current[z+1][y][x].e - &node.e) * LATICE_DENSITY as f32;
-                                derivative_b_z = (&current[z+1][y][x].b - &node.b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_z = (&current[z+1][y][x].e) * LATICE_DENSITY as f32 * 0.5;
-                                derivative_b_z = (&current[z+1][y][x].b) * LATICE_DENSITY as f32 * 0.5;
-                            }
-                        } 
-                    } else if z == row.len() - 1 {
-                        match boundary_condition {
-                            BoundaryCondition::Fit => {
-                                derivative_e_z = (&node.e - &current[z-1][y][x].e) * LATICE_DENSITY as f32;
-                                derivative_b_z = (&node.b - &current[z-1][y][x].b) * LATICE_DENSITY as f32;
-                            }
-                            BoundaryCondition::Clip => {
-                                derivative_e_z = &current[z-1][y][x].e * LATICE_DENSITY as f32 * -0.5;
-                                derivative_b_z = &current[z-1][y][x].b * LATICE_DENSITY as f32 * -0.5;
-                            }
-                        }
-                    } else {
-                        derivative_e_z = (&current[z+1][y][x].e - &current[z-1][y][x].e) * LATICE_DENSITY as f32 * 0.5;
-                        derivative_b_z = (&current[z+1][y][x].b - &current[z-1][y][x].b) * LATICE_DENSITY as f32 * 0.5;
-                    }
-
-                    let curl_e = Field3Vec { components: vec![
-                        derivative_e_y.components[2] - derivative_e_z.components[1],
-                        derivative_e_z.components[0] - derivative_e_x.components[2],
-                        derivative_e_x.components[1] - derivative_e_y.components[0]
-                    ] };
-
-                    let curl_b = Field3Vec { components: vec![
-                        derivative_b_y.components[2] - derivative_b_z.components[1],
-                        derivative_b_z.components[0] - derivative_b_x.components[2],
-                        derivative_b_x.components[1] - derivative_b_y.components[0]
-                    ] };
-
-
-                    //totalDivergence += (derivative_e_x.components[0] + derivative_e_y.components[1] + derivative_e_z.components[2]).abs();
-                    //totalDivergence += (derivative_b_x.components[0] + derivative_b_y.components[1] + derivative_b_z.components[2]).abs();
+        simulation.step();
 
-
-                    let new_node = SpaceData {
-                        b: &node.b - &(dt * curl_e),
-                        e: &node.e + &(dt * ((curl_b / (e0 * m0)) - (&field_object_currents[node.object_index] / e0))),
-                        object_index: node.object_index,
-                    };
-
-                    /*if node.object_index > 0 {
-                        eprintln!("i: {}", node.object_index);
-                        eprintln!("step: {}", steps - steps_left);
-                        eprintln!("J: {}", &field_object_currents[node.object_index] / e0);
-                    }*/
-                    
-
-                    next[z][y][x] = new_node.clone();
-
-
-                    if 
-                        (steps-steps_left) % time_culling_factor == 0 && 
-                        (x+1) % space_culling_factor as usize == 0 &&
-                        (y+1) % space_culling_factor as usize == 0 &&
-                        (z+1) % space_culling_factor as usize == 0
-                    {
-                        let _ = out_writer.write_all(&new_node.e.components[0].to_le_bytes());
-                        let _ = out_writer.write_all(&[0]);
-                        let _ = out_writer.write_all(&new_node.e.components[1].to_le_bytes());
-                        let _ = out_writer.write_all(&[0]);
-                        let _ = out_writer.write_all(&new_node.e.components[2].to_le_bytes());
-                        let _ = out_writer.write_all(&[1]);
-                        let _ = out_writer.write_all(&new_node.b.components[0].to_le_bytes());
-                        let _ = out_writer.write_all(&[0]);
-                        let _ = out_writer.write_all(&new_node.b.components[1].to_le_bytes());
-                        let _ = out_writer.write_all(&[0]);
-                        let _ = out_writer.write_all(&new_node.b.components[2].to_le_bytes());
-                        if z == current.len()-1 && y == plane.len()-1 && x == row.len()-1 {
-                            let _ = out_writer.write_all(&[5]);
-                        } else if y == plane.len()-1 && x == row.len()-1 {
-                            let _ = out_writer.write_all(&[4]);
-                        } else if x == row.len()-1 {
-                            let _ = out_writer.write_all(&[3]);
-                        } else {
-                            let _ = out_writer.write_all(&[2]);
-                        }
-                    }
-                }
-            }
+        if let Err(error) = outputs.write(&simulation) {
+            eprintln!();
+            eprintln!("Failed to write the simulation output: {error}");
+            return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
         }
-        //eprintln!("Divergence: {}", totalDivergence);
 
-        //totalDivergence = 0.0;
-
-        (current, next) = (next, current);
         steps_left -= 1;
-        update_progress_bar(steps - steps_left, steps, "Running simulation... ");
+        if !cli.quiet {
+            update_progress_bar(steps - steps_left, steps, "Running simulation... ");
+        }
+    }
+    if let Err(error) = outputs.finish() {
+        eprintln!("Failed to write the simulation output: {error}");
+        return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+    }
+    if !cli.quiet {
+        eprintln!();
+        eprintln!("Simulation Done");
     }
-    eprintln!();
 
-    eprintln!("Simulation Done");
+    // Dipole reports, the simulation's ports being in the order of the dipoles
+    let dipoles = manifest.objects.iter().filter_map(|object| match object {
+        Object::Dipole { reference_impedance, frequencies, samples_file, impedance_file, .. } => {
+            Some((*reference_impedance, frequencies, samples_file, impedance_file))
+        }
+        _ => None
+    });
+    for (port, (reference_impedance, frequencies, samples_file, impedance_file)) in simulation.ports().iter().zip(dipoles) {
+        if let Some(path) = samples_file {
+            if let Err(error) = write_report(path, |writer| port.write_samples(writer)) {
+                eprintln!("cannot write {}: {error}", path.display());
+                return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+            }
+        }
+        if let (Some(path), Some(frequencies)) = (impedance_file, frequencies) {
+            let frequencies = frequencies.values();
+            if let Err(error) = write_report(path, |writer| port.write_response(writer, &frequencies, reference_impedance)) {
+                eprintln!("cannot write {}: {error}", path.display());
+                return ExitCode::from(OUTPUT_ERROR_EXIT_CODE);
+            }
+        }
+    }
 
     ExitCode::SUCCESS
 }
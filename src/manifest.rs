@@ -0,0 +1,1124 @@
+use std::{collections::BTreeMap, fmt, fs::File, io::{self, BufReader, Read}, path::{Path, PathBuf}};
+use serde::{de, Deserialize, Deserializer};
+use crate::Field3Vec;
+use crate::antenna;
+use crate::format::Component;
+use crate::boundary::{BoundaryCondition, BoundaryConditions, FaceCondition, FaceConditions};
+use crate::material::{Material, Shape};
+use crate::probe::{Probe, ProbeFormat};
+use crate::solver::Solver;
+use crate::source::{Curve, SourceMode};
+use crate::waveform::Waveform;
+
+
+const DEFAULT_LATICE_DENSITY: f32 = 30.0;
+const DEFAULT_SIMULATION_SIDE_LENGTH: f32 = 1.0;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub constants: Constants,
+    // Named materials the objects can fill regions with
+    #[serde(default)]
+    pub materials: BTreeMap<String, Material>,
+    pub objects: Vec<Object>,
+    // Where the fields go, the command line's --format and --output when left out
+    #[serde(default)]
+    pub outputs: Vec<Output>,
+    // Points recorded every step
+    #[serde(default)]
+    pub probes: Option<Probes>,
+    // Planes and lines of the latice recorded every `time_culling_factor` steps
+    #[serde(default)]
+    pub monitors: Vec<Monitor>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Constants {
+    pub e0: f32,
+    pub m0: f32,
+    pub dt: f32,
+    pub steps: u32,
+    pub time_culling_factor: u32,
+    pub space_culling_factor: u32,
+    #[serde(default)]
+    pub solver: Solver,
+    // Nodes per unit length
+    #[serde(default = "default_latice_density")]
+    pub latice_density: AxisValues,
+    #[serde(default = "default_simulation_side_length")]
+    pub simulation_side_length: AxisValues,
+    // Resolved by `FaceConditions::resolve`, which needs the axis periods
+    pub boundary_condition: FaceConditions
+}
+
+fn default_latice_density() -> AxisValues {
+    AxisValues([DEFAULT_LATICE_DENSITY; 3])
+}
+
+fn default_simulation_side_length() -> AxisValues {
+    AxisValues([DEFAULT_SIMULATION_SIDE_LENGTH; 3])
+}
+
+// Either a single number (applied to every axis) or an [x, y, z] array
+#[derive(Clone, Copy)]
+pub struct AxisValues(pub [f32; 3]);
+impl<'de> Deserialize<'de> for AxisValues {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AxisValuesVisitor;
+        impl<'de> de::Visitor<'de> for AxisValuesVisitor {
+            type Value = AxisValues;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number or an [x, y, z] array of numbers")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<AxisValues, E> {
+                Ok(AxisValues([value as f32; 3]))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<AxisValues, E> {
+                Ok(AxisValues([value as f32; 3]))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<AxisValues, E> {
+                Ok(AxisValues([value as f32; 3]))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<AxisValues, A::Error> {
+                let values = <[f32; 3]>::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+                Ok(AxisValues(values))
+            }
+        }
+        deserializer.deserialize_any(AxisValuesVisitor)
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    X,
+    Y,
+    Z
+}
+impl Axis {
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2
+        }
+    }
+}
+
+// Locations are in latice nodes, the extents of material shapes in units of length. Where sources
+// share nodes, the one listed last drives them.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Object {
+    Point {
+        location: [usize; 3],
+        #[serde(rename = "E")]
+        e: [f32; 3],
+        #[serde(rename = "B")]
+        b: [f32; 3]
+    },
+    Plane {
+        axis: Axis,
+        location: usize,
+        #[serde(rename = "E")]
+        e: [f32; 3],
+        #[serde(rename = "B")]
+        b: [f32; 3]
+    },
+    // Either a straight line across the latice along `axis`, `location` being the position on the
+    // two other axes in order, or any `path`. Driven either by a sine of `angular_frequency` or by
+    // any `waveform`. Sources are soft unless their `mode` says otherwise.
+    Wire {
+        #[serde(default)]
+        axis: Option<Axis>,
+        #[serde(default)]
+        location: Option<[usize; 2]>,
+        #[serde(default)]
+        path: Option<WirePath>,
+        amplitude: f32,
+        #[serde(default)]
+        angular_frequency: Option<f32>,
+        #[serde(default)]
+        waveform: Option<Waveform>,
+        #[serde(default)]
+        mode: SourceMode
+    },
+    // Thin wire dipole along `axis` with its feed gap at `location`, `length` long in units of
+    // length. Reports go to the optional CSV files, the impedance at each of `frequencies`. Always
+    // hard, its arms and gap holding E outright, so `mode` can only be "hard".
+    Dipole {
+        axis: Axis,
+        location: [usize; 3],
+        length: f32,
+        voltage: f32,
+        waveform: Waveform,
+        #[serde(default = "default_reference_impedance")]
+        reference_impedance: f32,
+        #[serde(default)]
+        frequencies: Option<FrequencyRange>,
+        #[serde(default)]
+        samples_file: Option<PathBuf>,
+        #[serde(default)]
+        impedance_file: Option<PathBuf>,
+        #[serde(default)]
+        mode: Option<SourceMode>
+    },
+    // Hertzian dipoles at the node nearest `location` (in units of length), `amplitude` being
+    // their current (or magnetic current) times their length. Hard dipoles hold the field at
+    // `amplitude` instead.
+    #[serde(rename = "electric_dipole")]
+    ElectricDipole {
+        location: AxisValues,
+        orientation: [f32; 3],
+        amplitude: f32,
+        waveform: Waveform,
+        #[serde(default)]
+        mode: SourceMode
+    },
+    #[serde(rename = "magnetic_dipole")]
+    MagneticDipole {
+        location: AxisValues,
+        orientation: [f32; 3],
+        amplitude: f32,
+        waveform: Waveform,
+        #[serde(default)]
+        mode: SourceMode
+    },
+    // Plane wave injected through the surface of the box from `min` to `max` (in units of length),
+    // travelling along `direction` with E along `polarization`. Always soft, the surface having to
+    // let the scattered field out, so `mode` can only be "soft".
+    #[serde(rename = "plane_wave")]
+    PlaneWave {
+        min: AxisValues,
+        max: AxisValues,
+        direction: [f32; 3],
+        polarization: [f32; 3],
+        amplitude: f32,
+        waveform: Waveform,
+        #[serde(default)]
+        mode: Option<SourceMode>
+    },
+    Box {
+        min: AxisValues,
+        max: AxisValues,
+        material: String
+    },
+    Sphere {
+        center: AxisValues,
+        radius: f32,
+        material: String
+    },
+    Cylinder {
+        center: AxisValues,
+        radius: f32,
+        axis: Axis,
+        #[serde(default)]
+        length: Option<f32>,
+        material: String
+    },
+    Slab {
+        axis: Axis,
+        from: f32,
+        to: f32,
+        material: String
+    }
+}
+fn default_reference_impedance() -> f32 {
+    50.0
+}
+
+// `count` evenly spaced angular frequencies from `from` to `to`
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrequencyRange {
+    pub from: f32,
+    pub to: f32,
+    pub count: usize
+}
+impl FrequencyRange {
+    pub fn values(&self) -> Vec<f32> {
+        if self.count == 1 {
+            return vec![self.from];
+        }
+        (0..self.count).map(|i| self.from + (self.to - self.from) * i as f32 / (self.count - 1) as f32).collect()
+    }
+}
+
+// Path of a wire, in units of length like the extents of material shapes
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum WirePath {
+    Polyline {
+        points: Vec<AxisValues>,
+        #[serde(default)]
+        closed: bool
+    },
+    Loop {
+        center: AxisValues,
+        radius: f32,
+        axis: Axis
+    },
+    // Rises along `axis` from `center`
+    Helix {
+        center: AxisValues,
+        radius: f32,
+        axis: Axis,
+        pitch: f32,
+        turns: f32
+    }
+}
+impl WirePath {
+    pub fn curve(&self) -> Curve {
+        match self {
+            WirePath::Polyline { points, closed } => Curve::Polyline { points: points.iter().map(|point| point.0).collect(), closed: *closed },
+            WirePath::Loop { center, radius, axis } => Curve::Loop { center: center.0, radius: *radius, axis: axis.index() },
+            WirePath::Helix { center, radius, axis, pitch, turns } => Curve::Helix {
+                center: center.0, radius: *radius, axis: axis.index(), pitch: *pitch, turns: *turns
+            }
+        }
+    }
+}
+
+impl Object {
+    // The region a material object fills and the name of its material
+    pub fn shape(&self) -> Option<(Shape, &str)> {
+        match self {
+            Object::Box { min, max, material } => Some((Shape::Box { min: min.0, max: max.0 }, material)),
+            Object::Sphere { center, radius, material } => Some((Shape::Sphere { center: center.0, radius: *radius }, material)),
+            Object::Cylinder { center, radius, axis, length, material } => Some((
+                Shape::Cylinder { center: center.0, radius: *radius, axis: axis.index(), length: *length },
+                material
+            )),
+            Object::Slab { axis, from, to, material } => Some((Shape::Slab { axis: axis.index(), from: *from, to: *to }, material)),
+            _ => None
+        }
+    }
+
+    // Time dependence of a source object, None for the others or a wire with no waveform
+    pub fn waveform(&self) -> Option<Waveform> {
+        match self {
+            Object::Wire { waveform: Some(waveform), .. }
+            | Object::Dipole { waveform, .. }
+            | Object::ElectricDipole { waveform, .. }
+            | Object::MagneticDipole { waveform, .. }
+            | Object::PlaneWave { waveform, .. } => Some(waveform.clone()),
+            Object::Wire { angular_frequency: Some(angular_frequency), .. } => Some(Waveform::Sine { angular_frequency: *angular_frequency }),
+            _ => None
+        }
+    }
+
+    fn waveform_mut(&mut self) -> Option<&mut Waveform> {
+        match self {
+            Object::Wire { waveform, .. } => waveform.as_mut(),
+            Object::Dipole { waveform, .. }
+            | Object::ElectricDipole { waveform, .. }
+            | Object::MagneticDipole { waveform, .. }
+            | Object::PlaneWave { waveform, .. } => Some(waveform),
+            _ => None
+        }
+    }
+}
+
+
+// Culling factors default to the constants' and components to all of them. Streams without a file
+// go to the command line's --output, or stdout.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Output {
+    // Stream read by display.py, always holding every component
+    Legacy {
+        file: Option<PathBuf>,
+        time_culling_factor: Option<u32>,
+        space_culling_factor: Option<u32>
+    },
+    Binary {
+        file: Option<PathBuf>,
+        time_culling_factor: Option<u32>,
+        space_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    },
+    // HDF5 snapshots next to the XDMF file `file`
+    Hdf5 {
+        file: PathBuf,
+        time_culling_factor: Option<u32>,
+        space_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    },
+    // VTK image data snapshots gathered by the .pvd collection `file`
+    Vtk {
+        file: PathBuf,
+        time_culling_factor: Option<u32>,
+        space_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    },
+    // Legacy VTK snapshots named after `file`
+    VtkLegacy {
+        file: PathBuf,
+        time_culling_factor: Option<u32>,
+        space_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    }
+}
+impl Output {
+    // None for a stream going to --output or stdout
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Output::Legacy { file, .. } | Output::Binary { file, .. } => file.as_deref(),
+            Output::Hdf5 { file, .. } | Output::Vtk { file, .. } | Output::VtkLegacy { file, .. } => Some(file)
+        }
+    }
+
+    pub fn time_culling_factor(&self, constants: &Constants) -> u32 {
+        match self {
+            Output::Legacy { time_culling_factor, .. }
+            | Output::Binary { time_culling_factor, .. }
+            | Output::Hdf5 { time_culling_factor, .. }
+            | Output::Vtk { time_culling_factor, .. }
+            | Output::VtkLegacy { time_culling_factor, .. } => time_culling_factor.unwrap_or(constants.time_culling_factor)
+        }
+    }
+
+    pub fn space_culling_factor(&self, constants: &Constants) -> u32 {
+        match self {
+            Output::Legacy { space_culling_factor, .. }
+            | Output::Binary { space_culling_factor, .. }
+            | Output::Hdf5 { space_culling_factor, .. }
+            | Output::Vtk { space_culling_factor, .. }
+            | Output::VtkLegacy { space_culling_factor, .. } => space_culling_factor.unwrap_or(constants.space_culling_factor)
+        }
+    }
+
+    pub fn components(&self) -> Vec<Component> {
+        match self {
+            Output::Legacy { .. } => Component::ALL.to_vec(),
+            Output::Binary { components, .. }
+            | Output::Hdf5 { components, .. }
+            | Output::Vtk { components, .. }
+            | Output::VtkLegacy { components, .. } => components.clone().unwrap_or(Component::ALL.to_vec())
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Probes {
+    pub file: PathBuf,
+    #[serde(default)]
+    pub format: ProbeFormat,
+    pub points: Vec<ProbePoint>
+}
+
+// Takes either a location in units of length or an index in latice nodes, which can fall between
+// them. Components default to all of them.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbePoint {
+    pub name: String,
+    pub location: Option<AxisValues>,
+    pub index: Option<[f32; 3]>,
+    pub components: Option<Vec<Component>>
+}
+impl ProbePoint {
+    pub fn probe(&self, latice_density: [f32; 3]) -> Probe {
+        let point = match (self.location, self.index) {
+            (Some(location), _) => [0, 1, 2].map(|axis| location.0[axis] * latice_density[axis]),
+            (None, Some(index)) => index,
+            (None, None) => [0.0; 3]
+        };
+        Probe { name: self.name.clone(), point, components: self.components.clone().unwrap_or(Component::ALL.to_vec()) }
+    }
+}
+
+
+// Written as field files holding only the nodes monitored. Locations are in latice nodes, a line's
+// along the other two axes in order like a wire's. The time culling factor defaults to the
+// constants' and components to all of them.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Monitor {
+    Plane {
+        axis: Axis,
+        location: usize,
+        file: PathBuf,
+        time_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    },
+    Line {
+        axis: Axis,
+        location: [usize; 2],
+        file: PathBuf,
+        time_culling_factor: Option<u32>,
+        components: Option<Vec<Component>>
+    }
+}
+impl Monitor {
+    pub fn file(&self) -> &Path {
+        match self {
+            Monitor::Plane { file, .. } | Monitor::Line { file, .. } => file
+        }
+    }
+
+    pub fn time_culling_factor(&self, constants: &Constants) -> u32 {
+        match self {
+            Monitor::Plane { time_culling_factor, .. } | Monitor::Line { time_culling_factor, .. } => {
+                time_culling_factor.unwrap_or(constants.time_culling_factor)
+            }
+        }
+    }
+
+    pub fn components(&self) -> Vec<Component> {
+        match self {
+            Monitor::Plane { components, .. } | Monitor::Line { components, .. } => components.clone().unwrap_or(Component::ALL.to_vec())
+        }
+    }
+
+    // First node monitored and the number of them along each axis
+    pub fn region(&self, dimensions: [usize; 3]) -> ([usize; 3], [usize; 3]) {
+        match self {
+            Monitor::Plane { axis, location, .. } => {
+                let (mut origin, mut size) = ([0; 3], dimensions);
+                origin[axis.index()] = *location;
+                size[axis.index()] = 1;
+                (origin, size)
+            }
+            Monitor::Line { axis, location, .. } => {
+                let (mut origin, mut size) = ([0; 3], [1; 3]);
+                let axis = axis.index();
+                for (other, position) in (0..3).filter(|&other| other != axis).zip(location) {
+                    origin[other] = *position;
+                }
+                size[axis] = dimensions[axis];
+                (origin, size)
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum ManifestError {
+    // The file could not be opened or read
+    Io(io::Error),
+    // The file is not well-formed JSON
+    Syntax(serde_json::Error),
+    // Well-formed, but a field is missing, unknown or of the wrong type
+    Schema(serde_path_to_error::Error<serde_json::Error>),
+    // Well-typed, but a value the simulation cannot use
+    Invalid {
+        path: String,
+        message: String
+    }
+}
+impl ManifestError {
+    fn invalid(path: impl Into<String>, message: impl Into<String>) -> Self {
+        ManifestError::Invalid { path: path.into(), message: message.into() }
+    }
+
+    // Process exit code reported for each kind of error, 2 being taken by command line usage errors
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ManifestError::Io(_) => 3,
+            ManifestError::Syntax(_) => 4,
+            ManifestError::Schema(_) => 5,
+            ManifestError::Invalid { .. } => 6
+        }
+    }
+}
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io(error) => write!(f, "cannot read manifest: {error}"),
+            ManifestError::Syntax(error) => write!(f, "manifest is not valid JSON: {error}"),
+            ManifestError::Schema(error) => write!(f, "{}: {}", error.path(), error.inner()),
+            ManifestError::Invalid { path, message } => write!(f, "{path}: {message}")
+        }
+    }
+}
+impl std::error::Error for ManifestError {}
+
+
+impl Manifest {
+    // Only checks the manifest's shape and reads the files it refers to, `validate` checks its values.
+    // Files the manifest names, whether read or written, are relative to its own directory.
+    pub fn load(filename: &str) -> Result<Self, ManifestError> {
+        let file = File::open(filename).map_err(ManifestError::Io)?;
+        Self::from_reader(BufReader::new(file), Path::new(filename).parent().unwrap_or(Path::new("")))
+    }
+
+    // Same as `load`, the files the manifest refers to being relative to `directory`
+    pub fn from_reader(reader: impl Read, directory: &Path) -> Result<Self, ManifestError> {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let mut manifest: Manifest = serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+            match error.inner().classify() {
+                serde_json::error::Category::Data => ManifestError::Schema(error),
+                _ => ManifestError::Syntax(error.into_inner())
+            }
+        })?;
+        deserializer.end().map_err(ManifestError::Syntax)?;
+        manifest.resolve_paths(directory);
+        manifest.load_waveforms()?;
+        Ok(manifest)
+    }
+
+    // Joins every relative file the manifest names onto `directory`, so the same manifest reads and
+    // writes the same files wherever it is run from
+    pub fn resolve_paths(&mut self, directory: &Path) {
+        let resolve = |file: &mut PathBuf| *file = directory.join(&*file);
+        for output in &mut self.outputs {
+            match output {
+                Output::Legacy { file, .. } | Output::Binary { file, .. } => file.iter_mut().for_each(resolve),
+                Output::Hdf5 { file, .. } | Output::Vtk { file, .. } | Output::VtkLegacy { file, .. } => resolve(file)
+            }
+        }
+        for monitor in &mut self.monitors {
+            match monitor {
+                Monitor::Plane { file, .. } | Monitor::Line { file, .. } => resolve(file)
+            }
+        }
+        if let Some(probes) = &mut self.probes {
+            resolve(&mut probes.file);
+        }
+        for object in &mut self.objects {
+            if let Object::Dipole { samples_file, impedance_file, .. } = object {
+                samples_file.iter_mut().chain(impedance_file.iter_mut()).for_each(resolve);
+            }
+            if let Some(Waveform::Tabulated { file, .. }) = object.waveform_mut() {
+                resolve(file);
+            }
+        }
+    }
+
+    // Reads the samples of tabulated waveforms
+    pub fn load_waveforms(&mut self) -> Result<(), ManifestError> {
+        for (i, object) in self.objects.iter_mut().enumerate() {
+            if let Some(waveform) = object.waveform_mut() {
+                waveform.load_samples().map_err(|message| ManifestError::invalid(format!("objects[{i}].waveform.file"), message))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dimensions(&self) -> [usize; 3] {
+        let density = self.constants.latice_density.0;
+        let side_length = self.constants.simulation_side_length.0;
+        [0, 1, 2].map(|axis| (density[axis] * side_length[axis]).round() as usize)
+    }
+
+    pub fn boundary_conditions(&self) -> Result<BoundaryConditions, ManifestError> {
+        let dimensions = self.dimensions();
+        let periods = [0, 1, 2].map(|axis| dimensions[axis] as f32 / self.constants.latice_density.0[axis]);
+        self.constants.boundary_condition.resolve(periods)
+            .map_err(|message| ManifestError::invalid("constants.boundary_condition", message))
+    }
+
+    // Checks everything deserialization cannot: signs, ranges and locations against the latice
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        let constants = &self.constants;
+        for (name, value) in [("e0", constants.e0), ("m0", constants.m0), ("dt", constants.dt)] {
+            if value <= 0.0 || !value.is_finite() {
+                return Err(ManifestError::invalid(format!("constants.{name}"), format!("expected a positive number, found {value}")));
+            }
+        }
+        for (name, value) in [
+            ("steps", constants.steps),
+            ("time_culling_factor", constants.time_culling_factor),
+            ("space_culling_factor", constants.space_culling_factor)
+        ] {
+            if value == 0 {
+                return Err(ManifestError::invalid(format!("constants.{name}"), "expected a positive integer, found 0"));
+            }
+        }
+        for (name, values) in [
+            ("latice_density", constants.latice_density),
+            ("simulation_side_length", constants.simulation_side_length)
+        ] {
+            if let Some(value) = values.0.iter().find(|value| **value <= 0.0 || !value.is_finite()) {
+                return Err(ManifestError::invalid(format!("constants.{name}"), format!("expected positive numbers, found {value}")));
+            }
+        }
+        let dimensions = self.dimensions();
+        // Every axis needs at least one interior node for the central differences
+        if let Some(axis) = (0..3).find(|&axis| dimensions[axis] < 3) {
+            return Err(ManifestError::invalid(
+                "constants.simulation_side_length",
+                format!("the latice is only {} nodes along {}, at least 3 are needed", dimensions[axis], ["x", "y", "z"][axis])
+            ));
+        }
+
+        for faces in &constants.boundary_condition.0 {
+            for face in faces {
+                if let FaceCondition::Cpml(parameters) = face {
+                    parameters.validate().map_err(|message| ManifestError::invalid("constants.boundary_condition", message))?;
+                }
+            }
+        }
+        let boundary_conditions = self.boundary_conditions()?;
+        for (axis, faces) in boundary_conditions.iter().enumerate() {
+            let thickness: usize = faces.iter().map(|face| match face {
+                BoundaryCondition::Cpml(parameters) => parameters.thickness,
+                _ => 0
+            }).sum();
+            // Opposite layers must leave some of the domain between them
+            if thickness >= dimensions[axis] {
+                return Err(ManifestError::invalid(
+                    "constants.boundary_condition",
+                    format!("absorbing layers {thickness} nodes thick leave nothing of the {} nodes along {}", dimensions[axis], ["x", "y", "z"][axis])
+                ));
+            }
+        }
+
+        for (name, material) in &self.materials {
+            material.validate().map_err(|message| ManifestError::invalid(format!("materials.{name}"), message))?;
+        }
+
+        for (i, object) in self.objects.iter().enumerate() {
+            self.validate_object(object, dimensions).map_err(|(field, message)| {
+                ManifestError::invalid(format!("objects[{i}].{field}"), message)
+            })?;
+        }
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            self.validate_output(output, dimensions).map_err(|(field, message)| {
+                ManifestError::invalid(format!("outputs[{i}].{field}"), message)
+            })?;
+        }
+        if let Some((i, _)) = self.outputs.iter().enumerate().filter(|(_, output)| output.file().is_none()).nth(1) {
+            return Err(ManifestError::invalid(format!("outputs[{i}].file"), "only one output can go to --output or stdout"));
+        }
+
+        for (i, monitor) in self.monitors.iter().enumerate() {
+            self.validate_monitor(monitor, dimensions).map_err(|(field, message)| {
+                ManifestError::invalid(format!("monitors[{i}].{field}"), message)
+            })?;
+        }
+
+        if let Some(probes) = &self.probes {
+            if probes.points.is_empty() {
+                return Err(ManifestError::invalid("probes.points", "expected at least one point"));
+            }
+            for (i, point) in probes.points.iter().enumerate() {
+                self.validate_probe(point, &probes.points[..i], dimensions).map_err(|(field, message)| {
+                    ManifestError::invalid(format!("probes.points[{i}].{field}"), message)
+                })?;
+            }
+        }
+
+        // Two writers of the same file would truncate each other's
+        let mut files: Vec<(String, &Path)> = Vec::new();
+        for (i, output) in self.outputs.iter().enumerate() {
+            files.extend(output.file().map(|file| (format!("outputs[{i}].file"), file)));
+        }
+        for (i, monitor) in self.monitors.iter().enumerate() {
+            files.push((format!("monitors[{i}].file"), monitor.file()));
+        }
+        files.extend(self.probes.as_ref().map(|probes| ("probes.file".to_string(), probes.file.as_path())));
+        for (i, object) in self.objects.iter().enumerate() {
+            if let Object::Dipole { samples_file, impedance_file, .. } = object {
+                files.extend(samples_file.as_deref().map(|file| (format!("objects[{i}].samples_file"), file)));
+                files.extend(impedance_file.as_deref().map(|file| (format!("objects[{i}].impedance_file"), file)));
+            }
+        }
+        for (i, (path, file)) in files.iter().enumerate() {
+            if let Some((other, _)) = files[..i].iter().find(|(_, other)| other == file) {
+                return Err(ManifestError::invalid(path.clone(), format!("{} is already written by {other}", file.display())));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the offending field and what is wrong with it
+    fn validate_monitor(&self, monitor: &Monitor, dimensions: [usize; 3]) -> Result<(), (&'static str, String)> {
+        let (origin, _) = monitor.region(dimensions);
+        if (0..3).any(|axis| origin[axis] >= dimensions[axis]) {
+            return Err(("location", format!(
+                "the monitor lies outside the {}x{}x{} latice", dimensions[0], dimensions[1], dimensions[2]
+            )));
+        }
+        if monitor.time_culling_factor(&self.constants) == 0 {
+            return Err(("time_culling_factor", "expected a positive integer, found 0".to_string()));
+        }
+        validate_components(&monitor.components()).map_err(|message| ("components", message))?;
+        Ok(())
+    }
+
+    // Returns the offending field and what is wrong with it, `previous` being the points before it
+    fn validate_probe(&self, point: &ProbePoint, previous: &[ProbePoint], dimensions: [usize; 3]) -> Result<(), (&'static str, String)> {
+        if point.name.is_empty() || point.name.contains([',', '"']) {
+            return Err(("name", "expected a name with no commas or quotes".to_string()));
+        }
+        if previous.iter().any(|other| other.name == point.name) {
+            return Err(("name", format!("another point is already named \"{}\"", point.name)));
+        }
+        let field = match (point.location, point.index) {
+            (Some(_), None) => "location",
+            (None, Some(_)) => "index",
+            (Some(_), Some(_)) => return Err(("index", "a point takes either a location or an index, not both".to_string())),
+            (None, None) => return Err(("location", "a point needs a location or an index".to_string()))
+        };
+        let node = point.probe(self.constants.latice_density.0).point;
+        if (0..3).any(|axis| !(0.0..=(dimensions[axis] - 1) as f32).contains(&node[axis])) {
+            return Err((field, format!(
+                "node {node:?} lies outside the {}x{}x{} latice", dimensions[0], dimensions[1], dimensions[2]
+            )));
+        }
+        if let Some(components) = &point.components {
+            validate_components(components).map_err(|message| ("components", message))?;
+        }
+        Ok(())
+    }
+
+    // Returns the offending field and what is wrong with it
+    fn validate_output(&self, output: &Output, dimensions: [usize; 3]) -> Result<(), (&'static str, String)> {
+        if output.time_culling_factor(&self.constants) == 0 {
+            return Err(("time_culling_factor", "expected a positive integer, found 0".to_string()));
+        }
+        let space_culling_factor = output.space_culling_factor(&self.constants);
+        if space_culling_factor == 0 {
+            return Err(("space_culling_factor", "expected a positive integer, found 0".to_string()));
+        }
+        // Snapshots need at least one node along every axis
+        if !matches!(output, Output::Legacy { .. } | Output::Binary { .. }) && dimensions.iter().any(|&n| n < space_culling_factor as usize) {
+            return Err(("space_culling_factor", format!(
+                "culling by {space_culling_factor} leaves nothing of the {}x{}x{} latice", dimensions[0], dimensions[1], dimensions[2]
+            )));
+        }
+        validate_components(&output.components()).map_err(|message| ("components", message))?;
+        Ok(())
+    }
+
+    // Returns the offending field and what is wrong with it
+    fn validate_object(&self, object: &Object, dimensions: [usize; 3]) -> Result<(), (&'static str, String)> {
+        let outside = |location: &[usize], axes: &[usize]| -> Option<String> {
+            let inside = location.iter().zip(axes).all(|(&position, &axis)| position < dimensions[axis]);
+            if inside {
+                return None;
+            }
+            Some(format!(
+                "location {location:?} lies outside the {}x{}x{} latice",
+                dimensions[0], dimensions[1], dimensions[2]
+            ))
+        };
+        match object {
+            Object::Point { location, .. } => {
+                if let Some(message) = outside(location, &[0, 1, 2]) {
+                    return Err(("location", message));
+                }
+            }
+            Object::Plane { axis, location, .. } => {
+                if let Some(message) = outside(&[*location], &[axis.index()]) {
+                    return Err(("location", message));
+                }
+            }
+            Object::Wire { axis, location, path, angular_frequency, waveform, .. } => {
+                match (angular_frequency, waveform) {
+                    (Some(_), Some(_)) => return Err(("waveform", "a wire takes either an angular_frequency or a waveform, not both".to_string())),
+                    (None, None) => return Err(("waveform", "a wire needs an angular_frequency or a waveform".to_string())),
+                    (None, Some(waveform)) => waveform.validate().map_err(|message| ("waveform", message))?,
+                    _ => ()
+                }
+                match (axis, location, path) {
+                    (Some(axis), Some(location), None) => {
+                        let axes = match axis {
+                            Axis::X => [1, 2],
+                            Axis::Y => [0, 2],
+                            Axis::Z => [0, 1]
+                        };
+                        if let Some(message) = outside(location, &axes) {
+                            return Err(("location", message));
+                        }
+                    }
+                    (None, None, Some(path)) => {
+                        let curve = path.curve();
+                        curve.validate().map_err(|message| ("path", message))?;
+                        let density = self.constants.latice_density.0;
+                        let extent = [0, 1, 2].map(|axis| (dimensions[axis] - 1) as f32 / density[axis]);
+                        // Half a node of slack, as points are rounded to the nearest node
+                        let slack = [0, 1, 2].map(|axis| 0.5 / density[axis]);
+                        let outside = curve.vertices(f32::INFINITY).into_iter().find(|point| {
+                            (0..3).any(|axis| point[axis] < -slack[axis] || point[axis] > extent[axis] + slack[axis])
+                        });
+                        if let Some(point) = outside {
+                            return Err(("path", format!(
+                                "point {point:?} lies outside the {} x {} x {} simulation", extent[0], extent[1], extent[2]
+                            )));
+                        }
+                    }
+                    (_, _, Some(_)) => return Err(("path", "a wire takes either an axis and location or a path, not both".to_string())),
+                    _ => return Err(("location", "a wire needs an axis and location, or a path".to_string()))
+                }
+            }
+            Object::Dipole { axis, location, length, waveform, reference_impedance, frequencies, impedance_file, mode, .. } => {
+                if let Some(message) = outside(location, &[0, 1, 2]) {
+                    return Err(("location", message));
+                }
+                if *mode == Some(SourceMode::Soft) {
+                    return Err(("mode", "a dipole holds E across its gap and along its arms, it can only be hard".to_string()));
+                }
+                let axis = axis.index();
+                let arm_nodes = antenna::arm_nodes(*length, self.constants.latice_density.0[axis]);
+                if arm_nodes == 0 {
+                    return Err(("length", format!("{length} is too short for arms either side of the gap")));
+                }
+                if location[axis] < arm_nodes || location[axis] + arm_nodes >= dimensions[axis] {
+                    return Err(("length", format!("arms {arm_nodes} nodes long run out of the latice")));
+                }
+                // The current is measured from the fields around the gap
+                if (0..3).any(|other| other != axis && (location[other] == 0 || location[other] + 1 >= dimensions[other])) {
+                    return Err(("location", "the gap needs a node on every side of it".to_string()));
+                }
+                waveform.validate().map_err(|message| ("waveform", message))?;
+                if *reference_impedance <= 0.0 || !reference_impedance.is_finite() {
+                    return Err(("reference_impedance", "expected a positive number".to_string()));
+                }
+                match frequencies {
+                    Some(frequencies) if frequencies.count == 0 || frequencies.from < 0.0 || frequencies.to < frequencies.from => {
+                        return Err(("frequencies", "expected a count of at least 1 and 0 <= from <= to".to_string()));
+                    }
+                    None if impedance_file.is_some() => {
+                        return Err(("frequencies", "an impedance_file needs frequencies to report at".to_string()));
+                    }
+                    _ => ()
+                }
+            }
+            Object::ElectricDipole { location, orientation, waveform, .. }
+            | Object::MagneticDipole { location, orientation, waveform, .. } => {
+                let density = self.constants.latice_density.0;
+                let node = [0, 1, 2].map(|axis| (location.0[axis] * density[axis]).round());
+                if (0..3).any(|axis| node[axis] < 0.0 || node[axis] >= dimensions[axis] as f32) {
+                    return Err(("location", format!(
+                        "location {:?} lies outside the {} x {} x {} simulation",
+                        location.0,
+                        (dimensions[0] - 1) as f32 / density[0],
+                        (dimensions[1] - 1) as f32 / density[1],
+                        (dimensions[2] - 1) as f32 / density[2]
+                    )));
+                }
+                if orientation.iter().all(|&component| component == 0.0) {
+                    return Err(("orientation", "expected a non-zero vector".to_string()));
+                }
+                waveform.validate().map_err(|message| ("waveform", message))?;
+            }
+            Object::PlaneWave { min, max, direction, polarization, waveform, mode, .. } => {
+                if *mode == Some(SourceMode::Hard) {
+                    return Err(("mode", "a plane wave is injected through a surface the scattered field leaves by, it can only be soft".to_string()));
+                }
+                let direction = Field3Vec{ components: *direction };
+                let polarization = Field3Vec{ components: *polarization };
+                if direction.norm() == 0.0 {
+                    return Err(("direction", "expected a non-zero vector".to_string()));
+                }
+                if direction.cross(polarization).norm() <= 1e-6 * direction.norm() * polarization.norm() {
+                    return Err(("polarization", "expected a vector not parallel to the direction".to_string()));
+                }
+                waveform.validate().map_err(|message| ("waveform", message))?;
+                // Every node next to the surface must be an ordinary one, not a face's or an absorbing layer's
+                let density = self.constants.latice_density.0;
+                let boundary_conditions = self.boundary_conditions().map_err(|_| ("min", "invalid boundary conditions".to_string()))?;
+                for axis in 0..3 {
+                    let layers = boundary_conditions[axis].each_ref().map(|face| match face {
+                        BoundaryCondition::Cpml(parameters) => parameters.thickness as f32,
+                        _ => 0.0
+                    });
+                    let (low, high) = (min.0[axis] * density[axis], max.0[axis] * density[axis]);
+                    if low >= high {
+                        return Err(("max", format!("expected max to exceed min along {}", ["x", "y", "z"][axis])));
+                    }
+                    if low < layers[0] + 1.0 || high > (dimensions[axis] - 1) as f32 - layers[1] - 1.0 {
+                        return Err(("min", format!(
+                            "the box must stay a node clear of the faces and absorbing layers along {}", ["x", "y", "z"][axis]
+                        )));
+                    }
+                }
+            }
+            _ => ()
+        }
+        if let Some((shape, material)) = object.shape() {
+            if material != "vacuum" && !self.materials.contains_key(material) {
+                return Err(("material", format!("unknown material \"{material}\"")));
+            }
+            let negative_radius = match shape {
+                Shape::Sphere { radius, .. } | Shape::Cylinder { radius, .. } => radius < 0.0,
+                _ => false
+            };
+            if negative_radius {
+                return Err(("radius", "expected a non-negative number".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_components(components: &[Component]) -> Result<(), String> {
+    if components.is_empty() {
+        return Err("expected at least one component".to_string());
+    }
+    if let Some((_, component)) = components.iter().enumerate().find(|(i, component)| components[..*i].contains(component)) {
+        return Err(format!("{} is listed more than once", component.name()));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::*;
+
+    // A 10 node cube with nothing in it, `changes` replacing whole top level entries
+    fn manifest(changes: Value) -> Value {
+        let mut manifest = json!({
+            "constants": {
+                "e0": 1, "m0": 1, "dt": 0.01, "steps": 10, "time_culling_factor": 1, "space_culling_factor": 1,
+                "latice_density": 10, "boundary_condition": "clip"
+            },
+            "objects": []
+        });
+        for (key, value) in changes.as_object().unwrap() {
+            manifest[key] = value.clone();
+        }
+        manifest
+    }
+
+    fn load(manifest: &Value) -> Result<Manifest, ManifestError> {
+        let manifest = Manifest::from_reader(manifest.to_string().as_bytes(), Path::new(""))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    // Asserts the manifest is invalid at `path`
+    fn invalid_at(manifest: &Value, path: &str) {
+        match load(manifest) {
+            Err(error @ ManifestError::Invalid { .. }) => {
+                assert_eq!(error.exit_code(), 6);
+                assert!(error.to_string().starts_with(&format!("{path}: ")), "{error}");
+            }
+            Err(error) => panic!("expected {path} to be invalid, got {error}"),
+            Ok(_) => panic!("expected {path} to be invalid")
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let manifest = load(&manifest(json!({}))).unwrap();
+        assert_eq!(manifest.dimensions(), [10, 10, 10]);
+    }
+
+    #[test]
+    fn missing_file() {
+        let error = Manifest::load("/nonexistent/manifest.json").err().unwrap();
+        assert!(matches!(error, ManifestError::Io(_)));
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn not_json() {
+        let error = Manifest::from_reader(&b"{\"constants\": "[..], Path::new("")).err().unwrap();
+        assert!(matches!(error, ManifestError::Syntax(_)));
+        assert_eq!(error.exit_code(), 4);
+    }
+
+    #[test]
+    fn unknown_field() {
+        let mut manifest = manifest(json!({}));
+        manifest["constants"]["side_length"] = json!(1);
+        let error = load(&manifest).err().unwrap();
+        assert!(matches!(error, ManifestError::Schema(_)));
+        assert_eq!(error.exit_code(), 5);
+        assert!(error.to_string().starts_with("constants.side_length: unknown field `side_length`"), "{error}");
+    }
+
+    #[test]
+    fn cpml_thicker_than_the_latice() {
+        let mut manifest = manifest(json!({}));
+        manifest["constants"]["boundary_condition"] = json!({ "type": "cpml", "thickness": 5 });
+        invalid_at(&manifest, "constants.boundary_condition");
+        manifest["constants"]["boundary_condition"] = json!({ "type": "cpml", "thickness": 4 });
+        load(&manifest).unwrap();
+    }
+
+    #[test]
+    fn boundary_errors_name_the_parameter() {
+        for (boundary_condition, expected) in [
+            (json!({ "type": "cpml", "thickness": "6" }), "constants.boundary_condition: thickness: invalid type: string \"6\", expected usize"),
+            (json!({ "type": "cpml", "thicknes": 6 }), "constants.boundary_condition: thicknes: unknown field `thicknes`"),
+            (json!({ "type": "clip", "thickness": 6 }), "constants.boundary_condition: thickness: unknown field `thickness`"),
+            (json!({ "type": "absorbing" }), "constants.boundary_condition: type: unknown variant `absorbing`"),
+            (json!({ "x": "clip", "y": "clip", "z": { "type": "bloch", "wavevector": [1] } }), "constants.boundary_condition.z: wavevector: invalid type: sequence")
+        ] {
+            let mut manifest = manifest(json!({}));
+            manifest["constants"]["boundary_condition"] = boundary_condition;
+            let error = load(&manifest).err().unwrap();
+            assert_eq!(error.exit_code(), 5);
+            assert!(error.to_string().starts_with(expected), "{error}");
+        }
+        let mut manifest = manifest(json!({}));
+        manifest["constants"]["boundary_condition"] = json!({ "type": "cpml", "thickness": 2, "reflection": 2 });
+        invalid_at(&manifest, "constants.boundary_condition");
+    }
+
+    #[test]
+    fn probe_outside_the_latice() {
+        invalid_at(&manifest(json!({
+            "probes": { "file": "probes.csv", "points": [{ "name": "a", "index": [4, 4, 10] }] }
+        })), "probes.points[0].index");
+        invalid_at(&manifest(json!({
+            "probes": { "file": "probes.csv", "points": [{ "name": "a", "location": [0.5, 1.5, 0.5] }] }
+        })), "probes.points[0].location");
+    }
+
+    #[test]
+    fn overlapping_outputs() {
+        invalid_at(&manifest(json!({
+            "outputs": [{ "type": "legacy" }, { "type": "binary" }]
+        })), "outputs[1].file");
+        invalid_at(&manifest(json!({
+            "outputs": [{ "type": "binary", "file": "fields.max" }],
+            "monitors": [{ "type": "plane", "axis": "z", "location": 5, "file": "fields.max" }]
+        })), "monitors[0].file");
+        invalid_at(&manifest(json!({
+            "monitors": [{ "type": "line", "axis": "x", "location": [5, 5], "file": "line.max" }],
+            "probes": { "file": "line.max", "points": [{ "name": "a", "index": [1, 1, 1] }] }
+        })), "probes.file");
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_manifest() {
+        let manifest = Manifest::from_reader(manifest(json!({
+            "outputs": [{ "type": "legacy" }, { "type": "vtk", "file": "out/fields.pvd" }, { "type": "binary", "file": "/tmp/fields.max" }],
+            "monitors": [{ "type": "plane", "axis": "z", "location": 5, "file": "plane.max" }],
+            "probes": { "file": "probes.csv", "points": [{ "name": "a", "index": [1, 1, 1] }] }
+        })).to_string().as_bytes(), Path::new("runs/cavity")).unwrap();
+        let files: Vec<Option<&Path>> = manifest.outputs.iter().map(Output::file).collect();
+        assert_eq!(files, [None, Some(Path::new("runs/cavity/out/fields.pvd")), Some(Path::new("/tmp/fields.max"))]);
+        assert_eq!(manifest.monitors[0].file(), Path::new("runs/cavity/plane.max"));
+        assert_eq!(manifest.probes.unwrap().file, Path::new("runs/cavity/probes.csv"));
+    }
+
+    #[test]
+    fn non_finite_materials() {
+        for material in [
+            Material { permittivity: f32::NAN, ..Material::VACUUM },
+            Material { permeability: f32::INFINITY, ..Material::VACUUM },
+            Material { conductivity: f32::NAN, ..Material::VACUUM }
+        ] {
+            assert!(material.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn monitor_regions() {
+        let plane: Monitor = serde_json::from_value(json!({"type": "plane", "axis": "y", "location": 3, "file": "plane.bin"})).unwrap();
+        assert_eq!(plane.region([10, 12, 14]), ([0, 3, 0], [10, 1, 14]));
+        // A line's location is along x then z when it runs along y
+        let line: Monitor = serde_json::from_value(json!({"type": "line", "axis": "y", "location": [2, 5], "file": "line.bin"})).unwrap();
+        assert_eq!(line.region([10, 12, 14]), ([2, 0, 5], [1, 12, 1]));
+    }
+}
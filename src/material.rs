@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Deserialize;
+use crate::dispersion::Pole;
+
+
+// Electromagnetic properties of a medium, relative to the manifest's e0 and m0. Every property
+// is optional in the manifest and defaults to that of vacuum.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Material {
+    pub permittivity: f32,
+    pub permeability: f32,
+    // Electric conductivity, drains E
+    pub conductivity: f32,
+    // Magnetic conductivity, drains B
+    pub magnetic_conductivity: f32,
+    // Dispersive terms on top of `permittivity`, which is then the infinite-frequency permittivity
+    pub poles: Vec<Pole>
+}
+impl Default for Material {
+    fn default() -> Self {
+        Self::VACUUM
+    }
+}
+impl Material {
+    pub const VACUUM: Material = Material {
+        permittivity: 1.0,
+        permeability: 1.0,
+        conductivity: 0.0,
+        magnetic_conductivity: 0.0,
+        poles: Vec::new()
+    };
+
+    pub fn validate(&self) -> Result<(), String> {
+        if [self.permittivity, self.permeability].iter().any(|value| *value <= 0.0 || !value.is_finite()) {
+            return Err("permittivity and permeability must be positive".to_string());
+        }
+        if [self.conductivity, self.magnetic_conductivity].iter().any(|value| *value < 0.0 || !value.is_finite()) {
+            return Err("conductivities cannot be negative".to_string());
+        }
+        for (i, pole) in self.poles.iter().enumerate() {
+            pole.validate().map_err(|message| format!("poles[{i}]: {message}"))?;
+        }
+        Ok(())
+    }
+
+    // (Ca, Cb) for E' = Ca E + Cb (curl(B / permeability) / (e0 m0) - J / e0), the conductive loss
+    // being averaged over the step. Vacuum gives exactly (1, dt).
+    pub fn e_coefficients(&self, dt: f32, e0: f32) -> (f32, f32) {
+        let loss = self.conductivity * dt / (2.0 * e0 * self.permittivity);
+        ((1.0 - loss) / (1.0 + loss), dt / (self.permittivity * (1.0 + loss)))
+    }
+
+    // (Da, Db) for B' = Da B - Db curl E
+    pub fn b_coefficients(&self, dt: f32, m0: f32) -> (f32, f32) {
+        let loss = self.magnetic_conductivity * dt / (2.0 * m0 * self.permeability);
+        ((1.0 - loss) / (1.0 + loss), dt / (1.0 + loss))
+    }
+}
+
+// Lays out the manifest's named materials for indexing by node. The list starts with vacuum,
+// which is what every node is filled with until an object says otherwise.
+pub fn material_table(named: &BTreeMap<String, Material>) -> (Vec<Material>, HashMap<String, usize>) {
+    let mut materials = vec![Material::VACUUM];
+    let mut indices = HashMap::from([("vacuum".to_string(), 0)]);
+    for (name, material) in named {
+        if name == "vacuum" {
+            materials[0] = material.clone();
+            continue;
+        }
+        indices.insert(name.clone(), materials.len());
+        materials.push(material.clone());
+    }
+    (materials, indices)
+}
+
+
+// Region of space filled by a material object, in the same units as the simulation side length
+pub enum Shape {
+    Box {
+        min: [f32; 3],
+        max: [f32; 3]
+    },
+    Sphere {
+        center: [f32; 3],
+        radius: f32
+    },
+    // Circular cylinder around `axis`, infinitely long unless given a length
+    Cylinder {
+        center: [f32; 3],
+        radius: f32,
+        axis: usize,
+        length: Option<f32>
+    },
+    // Everything between two planes normal to `axis`
+    Slab {
+        axis: usize,
+        from: f32,
+        to: f32
+    }
+}
+impl Shape {
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        match self {
+            Shape::Box { min, max } => (0..3).all(|axis| min[axis] <= point[axis] && point[axis] <= max[axis]),
+            Shape::Sphere { center, radius } => {
+                (0..3).map(|axis| (point[axis] - center[axis]).powi(2)).sum::<f32>() <= radius * radius
+            }
+            Shape::Cylinder { center, radius, axis, length } => {
+                let radial = (0..3)
+                    .filter(|other| other != axis)
+                    .map(|other| (point[other] - center[other]).powi(2))
+                    .sum::<f32>();
+                let along = (point[*axis] - center[*axis]).abs();
+                radial <= radius * radius && length.is_none_or(|length| along <= 0.5 * length)
+            }
+            Shape::Slab { axis, from, to } => from.min(*to) <= point[*axis] && point[*axis] <= from.max(*to)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_includes_its_faces() {
+        let shape = Shape::Box { min: [0.25, 0.5, 0.0], max: [0.75, 1.0, 0.5] };
+        assert!(shape.contains([0.25, 0.5, 0.0]));
+        assert!(shape.contains([0.75, 1.0, 0.5]));
+        assert!(shape.contains([0.5, 0.75, 0.25]));
+        assert!(!shape.contains([0.125, 0.75, 0.25]));
+        assert!(!shape.contains([0.875, 0.75, 0.25]));
+        assert!(!shape.contains([0.5, 1.125, 0.25]));
+        assert!(!shape.contains([0.5, 0.75, -0.125]));
+    }
+
+    #[test]
+    fn sphere_includes_its_surface() {
+        let shape = Shape::Sphere { center: [1.0, 1.0, 1.0], radius: 0.5 };
+        assert!(shape.contains([1.0, 1.0, 1.0]));
+        assert!(shape.contains([1.5, 1.0, 1.0]));
+        assert!(shape.contains([1.0, 0.5, 1.0]));
+        assert!(shape.contains([1.0, 1.0, 0.5]));
+        assert!(!shape.contains([1.625, 1.0, 1.0]));
+        // Within the sphere's bounding box, but not the sphere
+        assert!(!shape.contains([1.375, 1.375, 1.375]));
+    }
+
+    #[test]
+    fn cylinder_includes_its_surface_and_caps() {
+        let shape = Shape::Cylinder { center: [1.0, 1.0, 1.0], radius: 0.5, axis: 2, length: Some(1.0) };
+        assert!(shape.contains([1.5, 1.0, 1.0]));
+        assert!(shape.contains([1.0, 0.5, 1.0]));
+        assert!(shape.contains([1.0, 1.0, 0.5]));
+        assert!(shape.contains([1.0, 1.0, 1.5]));
+        assert!(!shape.contains([1.625, 1.0, 1.0]));
+        assert!(!shape.contains([1.375, 1.375, 1.0]));
+        assert!(!shape.contains([1.0, 1.0, 1.625]));
+        assert!(!shape.contains([1.0, 1.0, 0.375]));
+
+        let endless = Shape::Cylinder { center: [1.0, 1.0, 1.0], radius: 0.5, axis: 0, length: None };
+        assert!(endless.contains([-100.0, 1.5, 1.0]));
+        assert!(endless.contains([100.0, 1.0, 0.5]));
+        assert!(!endless.contains([1.0, 1.625, 1.0]));
+    }
+
+    #[test]
+    fn slab_includes_its_planes() {
+        for (from, to) in [(0.5, 0.75), (0.75, 0.5)] {
+            let shape = Shape::Slab { axis: 1, from, to };
+            assert!(shape.contains([-3.0, 0.5, 7.0]));
+            assert!(shape.contains([3.0, 0.75, -7.0]));
+            assert!(!shape.contains([0.0, 0.375, 0.0]));
+            assert!(!shape.contains([0.0, 0.875, 0.0]));
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Write}, path::Path};
+use serde_json::Value;
+use crate::Simulation;
+use crate::format::{self, Component, FrameWriter, Header};
+use crate::manifest::{self, Manifest, Monitor};
+use crate::vtk::{VtkFormat, VtkWriter};
+use crate::xdmf::XdmfWriter;
+
+
+// Somewhere the simulated fields go, given the simulation's initial conditions and then every
+// frame kept
+pub trait OutputSink {
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()>;
+
+    // Once the simulation is over
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+// Sinks, each given the initial conditions and then every `time_culling_factor`th step
+#[derive(Default)]
+pub struct Outputs {
+    sinks: Vec<(Box<dyn OutputSink>, u32)>
+}
+impl Outputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, sink: Box<dyn OutputSink>, time_culling_factor: u32) -> &mut Self {
+        self.sinks.push((sink, time_culling_factor));
+        self
+    }
+
+    // After every step, and once before the first
+    pub fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        for (sink, time_culling_factor) in &mut self.sinks {
+            if simulation.steps_taken().is_multiple_of(*time_culling_factor) {
+                sink.write(simulation)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        for (sink, _) in &mut self.sinks {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
+
+
+// Opens the sink an output of the manifest describes, streams without a file of their own
+// writing to `stream`
+pub fn open(
+    output: &manifest::Output,
+    manifest: &Manifest,
+    manifest_filename: &str,
+    simulation: &Simulation,
+    stream: impl FnOnce() -> io::Result<Box<dyn Write>>
+) -> io::Result<Box<dyn OutputSink>> {
+    let constants = &manifest.constants;
+    let time_culling_factor = output.time_culling_factor(constants);
+    let space_culling_factor = output.space_culling_factor(constants);
+    let dimensions = format::culled_dimensions(simulation.dimensions(), space_culling_factor);
+    let spacing = simulation.latice_density().map(|density| space_culling_factor as f32 / density);
+    let components = output.components();
+    let writer = || -> io::Result<BufWriter<Box<dyn Write>>> {
+        let writer: Box<dyn Write> = match output.file() {
+            Some(path) => Box::new(create(path)?),
+            None => stream()?
+        };
+        Ok(BufWriter::new(writer))
+    };
+    Ok(match output {
+        manifest::Output::Legacy { .. } => Box::new(LegacyStream::new(writer()?, simulation, time_culling_factor, space_culling_factor)?),
+        manifest::Output::Binary { .. } => {
+            let header = Header {
+                dimensions,
+                origin: [space_culling_factor as usize - 1; 3],
+                spacing,
+                frame_dt: simulation.dt() * time_culling_factor as f32,
+                e0: constants.e0,
+                m0: constants.m0,
+                b_time_offset: simulation.solver().b_time_offset(simulation.dt()),
+                time_culling_factor,
+                space_culling_factor,
+                components,
+                manifest: simulated_manifest(manifest, manifest_filename)
+            };
+            Box::new(FrameWriter::new(writer()?, header)?)
+        }
+        manifest::Output::Hdf5 { file, .. } => Box::new(XdmfWriter::new(file, dimensions, spacing, space_culling_factor, &components)),
+        manifest::Output::Vtk { file, .. } => {
+            Box::new(VtkWriter::new(file, VtkFormat::Xml, dimensions, spacing, space_culling_factor, &components))
+        }
+        manifest::Output::VtkLegacy { file, .. } => {
+            Box::new(VtkWriter::new(file, VtkFormat::Legacy, dimensions, spacing, space_culling_factor, &components))
+        }
+    })
+}
+
+// Opens a field file holding only the nodes a monitor of the manifest covers
+pub fn open_monitor(monitor: &Monitor, manifest: &Manifest, manifest_filename: &str, simulation: &Simulation) -> io::Result<Box<dyn OutputSink>> {
+    let constants = &manifest.constants;
+    let time_culling_factor = monitor.time_culling_factor(constants);
+    let (origin, dimensions) = monitor.region(simulation.dimensions());
+    let header = Header {
+        dimensions,
+        origin,
+        spacing: simulation.latice_density().map(|density| 1.0 / density),
+        frame_dt: simulation.dt() * time_culling_factor as f32,
+        e0: constants.e0,
+        m0: constants.m0,
+        b_time_offset: simulation.solver().b_time_offset(simulation.dt()),
+        time_culling_factor,
+        space_culling_factor: 1,
+        components: monitor.components(),
+        manifest: simulated_manifest(manifest, manifest_filename)
+    };
+    Ok(Box::new(FrameWriter::new(BufWriter::new(create(monitor.file())?), header)?))
+}
+
+// The manifest file as simulated, its steps and time step being those of `manifest`, which the
+// command line may have overridden. Left as written unless they were.
+fn simulated_manifest(manifest: &Manifest, manifest_filename: &str) -> String {
+    let Ok(text) = fs::read_to_string(manifest_filename) else {
+        return String::new();
+    };
+    let Ok(mut copy) = serde_json::from_str::<Value>(&text) else {
+        return text;
+    };
+    let Some(constants) = copy.get_mut("constants").and_then(Value::as_object_mut) else {
+        return text;
+    };
+    // The f32's shortest decimal form, so that 0.01 is written as such rather than as 0.00999999977
+    let dt = manifest.constants.dt.to_string().parse::<f64>().ok().and_then(serde_json::Number::from_f64);
+    let simulated = [("steps", Value::from(manifest.constants.steps)), ("dt", dt.map_or(Value::Null, Value::Number))];
+    if simulated.iter().all(|(key, value)| constants.get(*key) == Some(value)) {
+        return text;
+    }
+    for (key, value) in simulated {
+        constants.insert(key.to_string(), value);
+    }
+    serde_json::to_string_pretty(&copy).unwrap_or(text)
+}
+
+// Names the file in the error, as the caller can't tell which of the outputs failed
+pub fn create(path: &Path) -> io::Result<File> {
+    File::create(path).map_err(|error| io::Error::new(error.kind(), format!("cannot create {}: {error}", path.display())))
+}
+
+
+// How snapshots store a selection of components: complete vectors whole, the others one by one
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Field {
+    Vector(&'static str, [Component; 3]),
+    Scalar(Component)
+}
+impl Field {
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Vector(name, _) => name,
+            Field::Scalar(component) => component.name()
+        }
+    }
+
+    pub fn components(&self) -> &[Component] {
+        match self {
+            Field::Vector(_, components) => components,
+            Field::Scalar(component) => std::slice::from_ref(component)
+        }
+    }
+
+    pub fn select(components: &[Component]) -> Vec<Field> {
+        let mut fields: Vec<Field> = [
+            ("E", [Component::Ex, Component::Ey, Component::Ez]),
+            ("B", [Component::Bx, Component::By, Component::Bz])
+        ].into_iter().filter(|(_, vector)| vector.iter().all(|component| components.contains(component))).map(|(name, vector)| {
+            Field::Vector(name, vector)
+        }).collect();
+        for &component in components {
+            if !fields.iter().any(|field| field.components().contains(&component)) {
+                fields.push(Field::Scalar(component));
+            }
+        }
+        fields
+    }
+}
+
+
+// Stream read by display.py: the stored dimensions and spacing and the time between frames, then
+// every frame's nodes, E and B each separated by a marker byte. The initial frame isn't space culled.
+pub struct LegacyStream<W: Write> {
+    writer: W,
+    space_culling_factor: usize,
+    initial: bool
+}
+impl<W: Write> LegacyStream<W> {
+    pub fn new(mut writer: W, simulation: &Simulation, time_culling_factor: u32, space_culling_factor: u32) -> io::Result<Self> {
+        // Send constants for data reconstruction
+        for n in simulation.dimensions() {
+            writer.write_all(&((n / space_culling_factor as usize) as u32).to_le_bytes())?;
+        }
+        for density in simulation.latice_density() {
+            writer.write_all(&(density / space_culling_factor as f32).to_le_bytes())?;
+        }
+        writer.write_all(&(simulation.dt() * time_culling_factor as f32).to_le_bytes())?;
+        Ok(Self { writer, space_culling_factor: space_culling_factor as usize, initial: true })
+    }
+}
+impl<W: Write> OutputSink for LegacyStream<W> {
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        let out_writer = &mut self.writer;
+        let [size_x, size_y, size_z] = simulation.dimensions();
+        let space_culling_factor = if self.initial { 1 } else { self.space_culling_factor };
+        self.initial = false;
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    if
+                        !(x+1).is_multiple_of(space_culling_factor) ||
+                        !(y+1).is_multiple_of(space_culling_factor) ||
+                        !(z+1).is_multiple_of(space_culling_factor)
+                    {
+                        continue;
+                    }
+                    let node = &simulation.latice()[(x, y, z)];
+                    out_writer.write_all(&node.e.components[0].to_le_bytes())?;
+                    out_writer.write_all(&[0])?;
+                    out_writer.write_all(&node.e.components[1].to_le_bytes())?;
+                    out_writer.write_all(&[0])?;
+                    out_writer.write_all(&node.e.components[2].to_le_bytes())?;
+                    out_writer.write_all(&[1])?;
+                    out_writer.write_all(&node.b.components[0].to_le_bytes())?;
+                    out_writer.write_all(&[0])?;
+                    out_writer.write_all(&node.b.components[1].to_le_bytes())?;
+                    out_writer.write_all(&[0])?;
+                    out_writer.write_all(&node.b.components[2].to_le_bytes())?;
+                    if z == size_z-1 && y == size_y-1 && x == size_x-1 {
+                        out_writer.write_all(&[5])?;
+                    } else if y == size_y-1 && x == size_x-1 {
+                        out_writer.write_all(&[4])?;
+                    } else if x == size_x-1 {
+                        out_writer.write_all(&[3])?;
+                    } else {
+                        out_writer.write_all(&[2])?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_vectors_are_kept_whole() {
+        let fields = Field::select(&[Component::Ex, Component::Ey, Component::Ez, Component::Bz]);
+        assert_eq!(fields, [Field::Vector("E", [Component::Ex, Component::Ey, Component::Ez]), Field::Scalar(Component::Bz)]);
+        assert_eq!(fields.iter().map(|field| field.name()).collect::<Vec<_>>(), ["E", "Bz"]);
+        assert_eq!(fields[1].components(), [Component::Bz]);
+    }
+
+    #[test]
+    fn incomplete_vectors_are_split() {
+        let fields = Field::select(&[Component::Bx, Component::Ez, Component::By]);
+        assert_eq!(fields, [Field::Scalar(Component::Bx), Field::Scalar(Component::Ez), Field::Scalar(Component::By)]);
+    }
+
+    #[test]
+    fn every_component() {
+        let fields = Field::select(&Component::ALL);
+        assert_eq!(fields.iter().map(|field| field.name()).collect::<Vec<_>>(), ["E", "B"]);
+        assert_eq!(fields.iter().flat_map(|field| field.components()).copied().collect::<Vec<_>>(), Component::ALL);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{Field3Vec, Latice};
+use crate::material::Material;
+use crate::solver::Solver;
+use crate::waveform::Waveform;
+
+
+// Plane wave travelling along `direction` with E along `polarization`, only present inside the box
+// from `min` to `max` (in units of length). Anything scattered by what the box holds leaves it.
+#[derive(Clone)]
+pub struct PlaneWave {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub direction: Field3Vec,
+    // Only the part perpendicular to `direction` counts
+    pub polarization: Field3Vec,
+    pub amplitude: f32,
+    pub waveform: Waveform
+}
+
+
+// Change to one component's curl at one node, from a neighbour lying on the other side of the
+// total field / scattered field surface
+struct Correction {
+    position: (usize, usize, usize),
+    component: usize,
+    // Where the neighbour's field lies, in units of length, and which of its components is used
+    point: [f32; 3],
+    incident_component: usize,
+    weight: f32
+}
+
+// Total field / scattered field surface injecting a plane wave. The nodes the wave crosses it at
+// hold the total field on one side and only the scattered field on the other, so the curls
+// reaching across it are corrected by the analytic incident field.
+pub struct TfsfSurface {
+    wave: PlaneWave,
+    direction: Field3Vec,
+    polarization: Field3Vec,
+    // Corner of the box the wave reaches first, at t = 0
+    origin: [f32; 3],
+    speed_of_light: f32,
+    e_corrections: Vec<Correction>,
+    b_corrections: Vec<Correction>
+}
+impl TfsfSurface {
+    pub fn new(wave: PlaneWave, dimensions: [usize; 3], latice_density: [f32; 3], solver: &Solver, speed_of_light: f32) -> Self {
+        let direction = wave.direction / wave.direction.norm();
+        let polarization = wave.polarization - wave.polarization.dot(direction) * direction;
+        let polarization = polarization / polarization.norm();
+        let origin = [0, 1, 2].map(|axis| if direction.components[axis] >= 0.0 { wave.min[axis] } else { wave.max[axis] });
+
+        let staggered = matches!(solver, Solver::Yee);
+        let offset = |is_e: bool, component: usize, axis: usize| solver.component_offset(is_e, component, axis);
+        // Neighbours along an axis a derivative takes and their weights, in latice spacings
+        let stencil = |is_e: bool| -> [(isize, f32); 2] {
+            match (staggered, is_e) {
+                (false, _) => [(1, 0.5), (-1, -0.5)],
+                // E takes the curl of B from the node and the one before it
+                (true, true) => [(0, 1.0), (-1, -1.0)],
+                // B takes the curl of E from the node and the one after it
+                (true, false) => [(1, 1.0), (0, -1.0)]
+            }
+        };
+        let inside = |point: [f32; 3]| (0..3).all(|axis| wave.min[axis] <= point[axis] && point[axis] <= wave.max[axis]);
+
+        let mut e_corrections = Vec::new();
+        let mut b_corrections = Vec::new();
+        let [size_x, size_y, size_z] = dimensions;
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let node = [x, y, z];
+                    for (is_e, corrections) in [(true, &mut e_corrections), (false, &mut b_corrections)] {
+                        for component in 0..3 {
+                            let point = [0, 1, 2].map(|axis| (node[axis] as f32 + offset(is_e, component, axis)) / latice_density[axis]);
+                            let total = inside(point);
+                            // curl_c = d_(c+1) G_(c+2) - d_(c+2) G_(c+1)
+                            for (axis, incident_component, curl_sign) in [
+                                ((component + 1) % 3, (component + 2) % 3, 1.0),
+                                ((component + 2) % 3, (component + 1) % 3, -1.0)
+                            ] {
+                                for (step, stencil_weight) in stencil(is_e) {
+                                    let along = node[axis] as isize + step;
+                                    if along < 0 || along >= dimensions[axis] as isize {
+                                        continue;
+                                    }
+                                    let mut neighbour = node;
+                                    neighbour[axis] = along as usize;
+                                    let neighbour_point = [0, 1, 2].map(|i| {
+                                        (neighbour[i] as f32 + offset(!is_e, incident_component, i)) / latice_density[i]
+                                    });
+                                    // The neighbour holds the scattered field where the total is needed, or the other way around
+                                    let sign = match (total, inside(neighbour_point)) {
+                                        (true, false) => 1.0,
+                                        (false, true) => -1.0,
+                                        _ => continue
+                                    };
+                                    corrections.push(Correction {
+                                        position: (x, y, z),
+                                        component,
+                                        point: neighbour_point,
+                                        incident_component,
+                                        weight: curl_sign * stencil_weight * latice_density[axis] * sign
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Self { wave, direction, polarization, origin, speed_of_light, e_corrections, b_corrections }
+    }
+
+    // Incident E and B at a point, nothing until the wave front reaches it
+    pub fn incident(&self, point: [f32; 3], t: f32) -> (Field3Vec, Field3Vec) {
+        let distance: f32 = (0..3).map(|axis| (point[axis] - self.origin[axis]) * self.direction.components[axis]).sum();
+        let retarded_time = t - distance / self.speed_of_light;
+        if retarded_time < 0.0 {
+            return (Field3Vec::default(), Field3Vec::default());
+        }
+        let e = self.wave.amplitude * self.wave.waveform.value(retarded_time) * self.polarization;
+        (e, self.direction.cross(e) / self.speed_of_light)
+    }
+
+    // After E has been advanced, `t` being the time of the B it was advanced with
+    pub fn correct_e(&self, latice: &mut Latice, materials: &[Material], t: f32, dt: f32, e0: f32, m0: f32) {
+        for correction in &self.e_corrections {
+            let (_, b) = self.incident(correction.point, t);
+            let node = &mut latice[correction.position];
+            let (_, gain) = materials[node.material_index].e_coefficients(dt, e0);
+            node.e.components[correction.component] += gain * correction.weight * b.components[correction.incident_component] / (e0 * m0);
+        }
+    }
+
+    // After B has been advanced, `t` being the time of the E it was advanced with
+    pub fn correct_b(&self, latice: &mut Latice, materials: &[Material], t: f32, dt: f32, m0: f32) {
+        for correction in &self.b_corrections {
+            let (e, _) = self.incident(correction.point, t);
+            let node = &mut latice[correction.position];
+            let (_, gain) = materials[node.material_index].b_coefficients(dt, m0);
+            node.b.components[correction.component] -= gain * correction.weight * e.components[correction.incident_component];
+        }
+    }
+}
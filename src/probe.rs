@@ -0,0 +1,218 @@
+use std::{fs::File, io::{self, BufWriter, Seek, SeekFrom, Write}, path::Path};
+use serde::Deserialize;
+use crate::{Latice, Simulation};
+use crate::format::Component;
+use crate::output::{self, OutputSink};
+use crate::solver::Solver;
+
+
+/*
+ * Binary probe file, every number little endian:
+ *   magic "MAXPROBE", version u32
+ *   column count u32, row count u64, only filled in once the simulation is over (until then 0,
+ *   the rows written following the header until the end of the file)
+ *   each column's name as a u32 length and UTF-8 bytes
+ * then a row after every step: the step as u32, then every other column as f32
+ * The columns are those of the CSV file: the step, E's time, B's time and every probed component.
+ */
+pub const MAGIC: &[u8; 8] = b"MAXPROBE";
+pub const VERSION: u32 = 1;
+// Where the row count is in the file
+const ROW_COUNT_OFFSET: u64 = 16;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeFormat {
+    #[default]
+    Csv,
+    Binary
+}
+
+
+// Named point the selected components are recorded at, in latice spacings from the first node
+#[derive(Clone, Debug)]
+pub struct Probe {
+    pub name: String,
+    pub point: [f32; 3],
+    pub components: Vec<Component>
+}
+impl Probe {
+    // Interpolated trilinearly from the nodes around the point, wherever the solver stores the
+    // component within them
+    pub fn value(&self, latice: &Latice, solver: &Solver, component: Component) -> f32 {
+        let mut corner = [0; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let along = self.point[axis] - solver.component_offset(component.is_electric(), component.axis(), axis);
+            let last = latice.dimensions[axis].saturating_sub(2);
+            corner[axis] = (along.floor().max(0.0) as usize).min(last);
+            weight[axis] = (along - corner[axis] as f32).clamp(0.0, 1.0);
+        }
+        let mut value = 0.0;
+        for (dx, dy, dz) in [(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0), (0, 0, 1), (1, 0, 1), (0, 1, 1), (1, 1, 1)] {
+            let node = &latice[(corner[0] + dx, corner[1] + dy, corner[2] + dz)];
+            let node_weight = [dx, dy, dz].iter().zip(weight).map(|(&step, weight)| if step == 1 { weight } else { 1.0 - weight }).product::<f32>();
+            value += node_weight * component.of(node);
+        }
+        value
+    }
+}
+
+
+// Records the probes after every step, a row at a time
+pub struct ProbeRecorder {
+    writer: BufWriter<File>,
+    format: ProbeFormat,
+    probes: Vec<Probe>,
+    rows: u64
+}
+impl ProbeRecorder {
+    pub fn new(path: &Path, format: ProbeFormat, probes: Vec<Probe>) -> io::Result<Self> {
+        let mut recorder = Self { writer: BufWriter::new(output::create(path)?), format, probes, rows: 0 };
+        let columns = recorder.columns();
+        match format {
+            ProbeFormat::Csv => writeln!(recorder.writer, "{}", columns.join(","))?,
+            ProbeFormat::Binary => {
+                recorder.writer.write_all(MAGIC)?;
+                recorder.writer.write_all(&VERSION.to_le_bytes())?;
+                recorder.writer.write_all(&(columns.len() as u32).to_le_bytes())?;
+                recorder.writer.write_all(&0u64.to_le_bytes())?;
+                for name in &columns {
+                    recorder.writer.write_all(&(name.len() as u32).to_le_bytes())?;
+                    recorder.writer.write_all(name.as_bytes())?;
+                }
+            }
+        }
+        Ok(recorder)
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns = vec!["step".to_string(), "time".to_string(), "b_time".to_string()];
+        for probe in &self.probes {
+            columns.extend(probe.components.iter().map(|component| format!("{}.{}", probe.name, component.name())));
+        }
+        columns
+    }
+}
+impl OutputSink for ProbeRecorder {
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        let solver = simulation.solver();
+        let (time, b_time) = (simulation.time(), simulation.b_time());
+        let values: Vec<f32> = self.probes.iter().flat_map(|probe| {
+            probe.components.iter().map(|&component| probe.value(simulation.latice(), &solver, component))
+        }).collect();
+        match self.format {
+            ProbeFormat::Csv => {
+                write!(self.writer, "{},{time},{b_time}", simulation.steps_taken())?;
+                for value in values {
+                    write!(self.writer, ",{value}")?;
+                }
+                writeln!(self.writer)
+            }
+            ProbeFormat::Binary => {
+                self.writer.write_all(&simulation.steps_taken().to_le_bytes())?;
+                for value in [time, b_time].into_iter().chain(values) {
+                    self.writer.write_all(&value.to_le_bytes())?;
+                }
+                self.rows += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.format == ProbeFormat::Binary {
+            let end = self.writer.stream_position()?;
+            self.writer.seek(SeekFrom::Start(ROW_COUNT_OFFSET))?;
+            self.writer.write_all(&self.rows.to_le_bytes())?;
+            self.writer.seek(SeekFrom::Start(end))?;
+        }
+        self.writer.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimulationBuilder;
+
+    fn linear(position: [f32; 3]) -> f32 {
+        1.0 + 2.0 * position[0] - 3.0 * position[1] + 0.5 * position[2]
+    }
+
+    // Every component follows the same linear field, sampled wherever the solver stores it
+    fn latice(solver: &Solver) -> Latice {
+        let mut latice = Latice::new([4, 5, 6]);
+        for z in 0..6 {
+            for y in 0..5 {
+                for x in 0..4 {
+                    for component in Component::ALL {
+                        let position = [x, y, z].map(|n| n as f32);
+                        let position = [0, 1, 2].map(|axis| {
+                            position[axis] + solver.component_offset(component.is_electric(), component.axis(), axis)
+                        });
+                        let node = &mut latice[(x, y, z)];
+                        let field = if component.is_electric() { &mut node.e } else { &mut node.b };
+                        field.components[component.axis()] = linear(position);
+                    }
+                }
+            }
+        }
+        latice
+    }
+
+    #[test]
+    fn interpolates_linear_fields_exactly() {
+        for solver in [Solver::Collocated, Solver::Yee] {
+            let latice = latice(&solver);
+            for point in [[1.0, 2.0, 3.0], [1.25, 2.5, 3.75], [0.6, 3.1, 4.2], [2.5, 3.5, 4.5]] {
+                let probe = Probe { name: "p".to_string(), point, components: Component::ALL.to_vec() };
+                for component in Component::ALL {
+                    let value = probe.value(&latice, &solver, component);
+                    assert!((value - linear(point)).abs() < 1e-4, "{} at {point:?}: {value}, not {}", component.name(), linear(point));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_to_the_latice() {
+        let latice = latice(&Solver::Collocated);
+        let probe = |point| Probe { name: "p".to_string(), point, components: vec![Component::Ex] };
+        let value = |point| probe(point).value(&latice, &Solver::Collocated, Component::Ex);
+        assert_eq!(value([-1.0, 0.0, 0.0]), linear([0.0, 0.0, 0.0]));
+        assert_eq!(value([3.0, 4.0, 5.0]), linear([3.0, 4.0, 5.0]));
+        assert_eq!(value([9.0, 4.0, 5.0]), linear([3.0, 4.0, 5.0]));
+    }
+
+    #[test]
+    fn binary_rows_stream_under_the_header() {
+        let path = std::env::temp_dir().join(format!("maximillion_probe_{}.bin", std::process::id()));
+        let mut builder = SimulationBuilder::new([4, 4, 4], [1.0; 3], 0.1);
+        builder.solver(Solver::Yee);
+        let mut simulation = builder.build();
+        let probes = vec![Probe { name: "centre".to_string(), point: [1.5, 1.5, 1.5], components: vec![Component::Ez, Component::By] }];
+        let mut recorder = ProbeRecorder::new(&path, ProbeFormat::Binary, probes).unwrap();
+        let header_len = 8 + 4 + 4 + 8 + recorder.columns().iter().map(|name| 4 + name.len()).sum::<usize>();
+        recorder.write(&simulation).unwrap();
+        simulation.step();
+        recorder.write(&simulation).unwrap();
+        recorder.writer.flush().unwrap();
+        // Rows are on disk before the simulation is over, the row count only once it is
+        let file = std::fs::read(&path).unwrap();
+        assert_eq!(file.len(), header_len + 2 * 5 * 4);
+        assert_eq!(&file[16..24], &0u64.to_le_bytes());
+        recorder.finish().unwrap();
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&file[..8], MAGIC);
+        assert_eq!(&file[12..16], &5u32.to_le_bytes());
+        assert_eq!(&file[16..24], &2u64.to_le_bytes());
+        assert_eq!(file.len(), header_len + 2 * 5 * 4);
+        let row = &file[header_len + 20..];
+        assert_eq!(&row[..4], &1u32.to_le_bytes());
+        assert_eq!(&row[4..8], &0.1f32.to_le_bytes());
+        assert_eq!(&row[8..12], &0.05f32.to_le_bytes());
+    }
+}
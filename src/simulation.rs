@@ -0,0 +1,421 @@
+use crate::{Field3Vec, Latice, SpaceData};
+use crate::antenna::{self, Dipole, Port};
+use crate::boundary::{BoundaryCondition, BoundaryConditions};
+use crate::manifest::{Axis, Manifest, ManifestError, Object};
+use crate::material::{self, Material, Shape};
+use crate::plane_wave::{PlaneWave, TfsfSurface};
+use crate::solver::{Fields, Solver, StepContext};
+use crate::source::{CurrentObject, CurveWire, ElectricDipole, Hard, MagneticDipole, SourceMode, SourceRegistry, Wire};
+
+
+// Sets up the initial state of a simulation, which cannot change shape once built
+pub struct SimulationBuilder {
+    latice: Latice,
+    latice_density: [f32; 3],
+    dt: f32,
+    e0: f32,
+    m0: f32,
+    solver: Solver,
+    boundary_conditions: BoundaryConditions,
+    materials: Vec<Material>,
+    sources: SourceRegistry,
+    ports: Vec<Port>,
+    plane_waves: Vec<PlaneWave>
+}
+impl SimulationBuilder {
+    // Starts from empty vacuum with e0 = m0 = 1, clipped faces and the collocated solver.
+    // Every axis needs at least 3 nodes.
+    pub fn new(dimensions: [usize; 3], latice_density: [f32; 3], dt: f32) -> Self {
+        assert!(dimensions.iter().all(|&n| n >= 3), "every axis of the latice needs at least 3 nodes");
+        Self {
+            latice: Latice::new(dimensions),
+            latice_density,
+            dt,
+            e0: 1.0,
+            m0: 1.0,
+            solver: Solver::default(),
+            boundary_conditions: [0, 1, 2].map(|_| [BoundaryCondition::Clip, BoundaryCondition::Clip]),
+            materials: vec![Material::VACUUM],
+            sources: SourceRegistry::default(),
+            ports: Vec::new(),
+            plane_waves: Vec::new()
+        }
+    }
+
+    pub fn solver(&mut self, solver: Solver) -> &mut Self {
+        self.solver = solver;
+        self
+    }
+
+    pub fn constants(&mut self, e0: f32, m0: f32) -> &mut Self {
+        self.e0 = e0;
+        self.m0 = m0;
+        self
+    }
+
+    pub fn boundary_conditions(&mut self, boundary_conditions: BoundaryConditions) -> &mut Self {
+        self.boundary_conditions = boundary_conditions;
+        self
+    }
+
+    // Returns the index to `fill` with, vacuum being 0
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    // Fills every node inside `shape` (in units of length) with a material
+    pub fn fill(&mut self, shape: &Shape, material_index: usize) -> &mut Self {
+        assert!(material_index < self.materials.len(), "no material {material_index}");
+        let [size_x, size_y, size_z] = self.latice.dimensions;
+        let density = self.latice_density;
+        for z in 0..size_z {
+            for y in 0..size_y {
+                for x in 0..size_x {
+                    let point = [x as f32 / density[0], y as f32 / density[1], z as f32 / density[2]];
+                    if shape.contains(point) {
+                        self.latice[(x, y, z)].material_index = material_index;
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    // Initial value of the fields at a node
+    pub fn set_fields(&mut self, position: (usize, usize, usize), e: Field3Vec, b: Field3Vec) -> &mut Self {
+        self.latice[position] = SpaceData {
+            e,
+            b,
+            ..self.latice[position]
+        };
+        self
+    }
+
+    // Drives `cells` with the current density of `source`, returns the source's index. Cells an
+    // earlier source drove are taken from it.
+    pub fn add_source(&mut self, source: Box<dyn CurrentObject>, cells: &[(usize, usize, usize)]) -> usize {
+        self.sources.register(source, cells, &mut self.latice)
+    }
+
+    // Records the voltage and current across a dipole's gap every step, returns the port's index
+    pub fn add_port(&mut self, port: Port) -> usize {
+        self.ports.push(port);
+        self.ports.len() - 1
+    }
+
+    // Injects a plane wave through the surface of its box, which should lie in vacuum
+    pub fn add_plane_wave(&mut self, plane_wave: PlaneWave) -> &mut Self {
+        self.plane_waves.push(plane_wave);
+        self
+    }
+
+    pub fn build(self) -> Simulation {
+        let speed_of_light = 1.0 / (self.e0 * self.m0).sqrt();
+        let dimensions = self.latice.dimensions;
+        let plane_waves = self.plane_waves.into_iter().map(|plane_wave| {
+            TfsfSurface::new(plane_wave, dimensions, self.latice_density, &self.solver, speed_of_light)
+        }).collect();
+        let fields = Fields::new(
+            self.latice, &self.solver, &self.boundary_conditions, &self.materials, self.latice_density, self.dt, speed_of_light
+        );
+        Simulation {
+            latice_density: self.latice_density,
+            dt: self.dt,
+            e0: self.e0,
+            m0: self.m0,
+            solver: self.solver,
+            boundary_conditions: self.boundary_conditions,
+            materials: self.materials,
+            sources: self.sources,
+            ports: self.ports,
+            plane_waves,
+            fields,
+            steps_taken: 0
+        }
+    }
+}
+
+
+pub struct Simulation {
+    latice_density: [f32; 3],
+    dt: f32,
+    e0: f32,
+    m0: f32,
+    solver: Solver,
+    boundary_conditions: BoundaryConditions,
+    materials: Vec<Material>,
+    sources: SourceRegistry,
+    ports: Vec<Port>,
+    plane_waves: Vec<TfsfSurface>,
+    fields: Fields,
+    steps_taken: u32
+}
+impl Simulation {
+    pub fn from_manifest(manifest: &Manifest) -> Result<Self, ManifestError> {
+        manifest.validate()?;
+        let constants = &manifest.constants;
+        let dimensions = manifest.dimensions();
+        let mut builder = SimulationBuilder::new(dimensions, constants.latice_density.0, constants.dt);
+        builder
+            .solver(constants.solver)
+            .constants(constants.e0, constants.m0)
+            .boundary_conditions(manifest.boundary_conditions()?);
+        let (materials, material_indices) = material::material_table(&manifest.materials);
+        builder.materials = materials;
+
+        let [size_x, size_y, size_z] = dimensions;
+
+        // Configure initial conditions
+        for object in &manifest.objects {
+            match object {
+                Object::Point { location, e, b } => {
+                    builder.set_fields((location[0], location[1], location[2]), Field3Vec{ components: *e }, Field3Vec{ components: *b });
+                }
+                Object::Plane { axis, location, e, b } => {
+                    let location = *location;
+                    // (i, j) span the two axes perpendicular to the plane's normal
+                    let (size_i, size_j) = match axis {
+                        Axis::X => (size_z, size_y),
+                        Axis::Y => (size_z, size_x),
+                        Axis::Z => (size_x, size_y)
+                    };
+                    for i in 0..size_i {
+                        for j in 0..size_j {
+                            let position = match axis {
+                                Axis::X => (location, j, i),
+                                Axis::Y => (j, location, i),
+                                Axis::Z => (i, j, location)
+                            };
+                            builder.set_fields(position, Field3Vec{ components: *e }, Field3Vec{ components: *b });
+                        }
+                    }
+                }
+                Object::Wire { path: Some(path), amplitude, mode, .. } => {
+                    let nodes = path.curve().rasterize(dimensions, constants.latice_density.0);
+                    let cells: Vec<_> = nodes.iter().map(|&(position, _)| position).collect();
+                    builder.add_source(with_mode(Box::new(CurveWire{
+                        amplitude: *amplitude,
+                        waveform: object.waveform().unwrap(),
+                        directions: nodes.into_iter().collect()
+                    }), *mode), &cells);
+                }
+                Object::Wire { axis: Some(axis), location: Some(location), amplitude, mode, .. } => {
+                    let cells: Vec<_> = (0..dimensions[axis.index()]).map(|i| match axis {
+                        Axis::X => (i, location[0], location[1]),
+                        Axis::Y => (location[0], i, location[1]),
+                        Axis::Z => (location[0], location[1], i)
+                    }).collect();
+                    let mut direction = Field3Vec::default();
+                    direction.components[axis.index()] = 1.0;
+                    // One source for the whole wire
+                    builder.add_source(with_mode(Box::new(Wire{
+                        amplitude: *amplitude,
+                        // Validation has already checked the wire has exactly one
+                        waveform: object.waveform().unwrap(),
+                        direction
+                    }), *mode), &cells);
+                }
+                Object::ElectricDipole { location, orientation, amplitude, waveform, mode }
+                | Object::MagneticDipole { location, orientation, amplitude, waveform, mode } => {
+                    let density = constants.latice_density.0;
+                    let node = [0, 1, 2].map(|axis| (location.0[axis] * density[axis]).round() as usize);
+                    let orientation = Field3Vec{ components: *orientation };
+                    let strength = match mode {
+                        // Spread over the volume of the node's cell
+                        SourceMode::Soft => amplitude * density.iter().product::<f32>(),
+                        // The field the node is held at
+                        SourceMode::Hard => *amplitude
+                    };
+                    let moment = strength / orientation.norm() * orientation;
+                    let waveform = waveform.clone();
+                    let source: Box<dyn CurrentObject> = match object {
+                        Object::ElectricDipole { .. } => Box::new(ElectricDipole{ moment, waveform }),
+                        _ => Box::new(MagneticDipole{ moment, waveform })
+                    };
+                    builder.add_source(with_mode(source, *mode), &[(node[0], node[1], node[2])]);
+                }
+                Object::PlaneWave { min, max, direction, polarization, amplitude, waveform, .. } => {
+                    builder.add_plane_wave(PlaneWave {
+                        min: min.0,
+                        max: max.0,
+                        direction: Field3Vec{ components: *direction },
+                        polarization: Field3Vec{ components: *polarization },
+                        amplitude: *amplitude,
+                        waveform: waveform.clone()
+                    });
+                }
+                Object::Dipole { axis, location, length, voltage, waveform, .. } => {
+                    let axis = axis.index();
+                    let feed = (location[0], location[1], location[2]);
+                    let arm_nodes = antenna::arm_nodes(*length, constants.latice_density.0[axis]);
+                    builder.add_source(Box::new(Dipole{
+                        feed,
+                        axis,
+                        voltage: *voltage,
+                        waveform: waveform.clone(),
+                        gap: 1.0 / constants.latice_density.0[axis]
+                    }), &antenna::dipole_cells(feed, axis, arm_nodes));
+                    // Ports are in the order of the dipoles in the manifest
+                    builder.add_port(Port::new(feed, axis));
+                }
+                _ => {
+                    // Validation has already checked the material exists
+                    let (shape, material_name) = object.shape().unwrap();
+                    // Later objects are painted over earlier ones
+                    builder.fill(&shape, material_indices[material_name]);
+                }
+            }
+        }
+        Ok(builder.build())
+    }
+
+    // Advances the fields by one time step
+    pub fn step(&mut self) {
+        self.steps_taken += 1;
+        let context = StepContext {
+            dt: self.dt,
+            e0: self.e0,
+            m0: self.m0,
+            latice_density: self.latice_density,
+            boundary_conditions: &self.boundary_conditions,
+            materials: &self.materials,
+            source_time: self.steps_taken as f32 * self.dt + self.solver.source_time_offset(self.dt),
+            magnetic_source_time: self.steps_taken as f32 * self.dt + self.solver.magnetic_source_time_offset(self.dt),
+            field_time: self.steps_taken as f32 * self.dt,
+            b_field_time: self.b_time(),
+            sources: &self.sources,
+            plane_waves: &self.plane_waves
+        };
+        self.fields.step(&self.solver, &context);
+        let time = self.time();
+        for port in &mut self.ports {
+            port.record(&self.fields.real.latice, &self.solver, self.latice_density, self.m0, time, self.dt);
+        }
+    }
+
+    pub fn run(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    // Drives `cells` with the current density of `source` from the next step on, returns the source's
+    // index. Cells an earlier source drove are taken from it.
+    pub fn add_source(&mut self, source: Box<dyn CurrentObject>, cells: &[(usize, usize, usize)]) -> usize {
+        let object_index = self.sources.register(source, cells, &mut self.fields.real.latice);
+        if let Some(imaginary) = self.fields.imaginary.as_mut() {
+            for &position in cells {
+                imaginary.latice[position].object_index = object_index;
+            }
+        }
+        object_index
+    }
+
+    pub fn sources(&self) -> &SourceRegistry {
+        &self.sources
+    }
+
+    pub fn ports(&self) -> &[Port] {
+        &self.ports
+    }
+
+    // The fields, the real part of them when Bloch-periodic
+    pub fn latice(&self) -> &Latice {
+        &self.fields.real.latice
+    }
+
+    pub fn e(&self, position: (usize, usize, usize)) -> Field3Vec {
+        self.fields.real.latice[position].e
+    }
+
+    pub fn b(&self, position: (usize, usize, usize)) -> Field3Vec {
+        self.fields.real.latice[position].b
+    }
+
+    // Imaginary part of the fields, only carried with a Bloch-periodic boundary
+    pub fn imaginary_latice(&self) -> Option<&Latice> {
+        self.fields.imaginary.as_ref().map(|imaginary| &imaginary.latice)
+    }
+
+    pub fn dimensions(&self) -> [usize; 3] {
+        self.fields.real.latice.dimensions
+    }
+
+    pub fn latice_density(&self) -> [f32; 3] {
+        self.latice_density
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    pub fn solver(&self) -> Solver {
+        self.solver
+    }
+
+    pub fn steps_taken(&self) -> u32 {
+        self.steps_taken
+    }
+
+    pub fn time(&self) -> f32 {
+        self.steps_taken as f32 * self.dt
+    }
+
+    // Time the stored B is at, behind E on the staggered solver
+    pub fn b_time(&self) -> f32 {
+        self.time() + self.solver.b_time_offset(self.dt)
+    }
+}
+
+fn with_mode(source: Box<dyn CurrentObject>, mode: SourceMode) -> Box<dyn CurrentObject> {
+    match mode {
+        SourceMode::Soft => source,
+        SourceMode::Hard => Box::new(Hard(source))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_sets_the_material_inside_the_shape_only() {
+        // Unequal densities, so that each axis must be scaled by its own
+        let mut builder = SimulationBuilder::new([9, 5, 9], [4.0, 2.0, 4.0], 0.01);
+        let glass = builder.add_material(Material { permittivity: 4.0, ..Material::VACUUM });
+        let metal = builder.add_material(Material { conductivity: 10.0, ..Material::VACUUM });
+        builder.fill(&Shape::Box { min: [0.5, 0.5, 0.5], max: [1.0, 1.0, 1.25] }, glass);
+        builder.fill(&Shape::Sphere { center: [2.0, 2.0, 2.0], radius: 0.25 }, metal);
+        let simulation = builder.build();
+        let latice = simulation.latice();
+        for z in 0..9 {
+            for y in 0..5 {
+                for x in 0..9 {
+                    let expected = if (2..=4).contains(&x) && (1..=2).contains(&y) && (2..=5).contains(&z) {
+                        glass
+                    } else if x >= 7 && y == 4 && z >= 7 && (8 - x) + (8 - z) <= 1 {
+                        metal
+                    } else {
+                        0
+                    };
+                    assert_eq!(latice[(x, y, z)].material_index, expected, "node {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn later_fills_take_over() {
+        let mut builder = SimulationBuilder::new([5, 5, 5], [4.0; 3], 0.01);
+        let glass = builder.add_material(Material { permittivity: 4.0, ..Material::VACUUM });
+        builder.fill(&Shape::Slab { axis: 0, from: 0.0, to: 0.5 }, glass);
+        builder.fill(&Shape::Slab { axis: 0, from: 0.5, to: 0.5 }, 0);
+        let simulation = builder.build();
+        for x in 0..5 {
+            let expected = if x < 2 { glass } else { 0 };
+            assert_eq!(simulation.latice()[(x, 3, 1)].material_index, expected, "node {x}");
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use serde::Deserialize;
+use crate::{Field3Vec, Latice, SpaceData};
+use crate::boundary::{self, BoundaryCondition, BoundaryConditions, Cpml, Field, Neighbors};
+use crate::dispersion::Dispersion;
+use crate::material::Material;
+use crate::plane_wave::TfsfSurface;
+use crate::source::{SourceContext, SourceRegistry};
+
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Solver {
+    // Every component lives on the node, both fields advanced together with forward Euler, which
+    // is unstable. Only the default so manifests written before the Yee solver run as they did.
+    #[default]
+    Collocated,
+    // Staggered Yee grid: E on cell edges, B on face centers, B leapfrogging half a step behind E
+    Yee
+}
+impl Solver {
+    // Offset from the step time at which source currents should be sampled
+    pub fn source_time_offset(&self, dt: f32) -> f32 {
+        match self {
+            Solver::Collocated => 0.0,
+            Solver::Yee => -0.5 * dt
+        }
+    }
+
+    // Offset from the step time of the stored B, which leapfrogs half a step behind E on the
+    // staggered solver
+    pub fn b_time_offset(&self, dt: f32) -> f32 {
+        match self {
+            Solver::Collocated => 0.0,
+            Solver::Yee => -0.5 * dt
+        }
+    }
+
+    // Offset from the step time at which magnetic source currents should be sampled, B being
+    // advanced with the E of the previous step
+    pub fn magnetic_source_time_offset(&self, dt: f32) -> f32 {
+        match self {
+            Solver::Collocated => 0.0,
+            Solver::Yee => -dt
+        }
+    }
+
+    // Where a component of E (or else B) lies within its node along `axis`, in latice spacings
+    pub fn component_offset(&self, is_e: bool, component: usize, axis: usize) -> f32 {
+        match (self, is_e) {
+            (Solver::Collocated, _) => 0.0,
+            (Solver::Yee, true) => if axis == component { 0.5 } else { 0.0 },
+            (Solver::Yee, false) => if axis == component { 0.0 } else { 0.5 }
+        }
+    }
+
+    // Offset (in nodes) of the point where the derivatives of E are evaluated
+    pub fn e_derivative_offset(&self) -> f32 {
+        match self {
+            Solver::Collocated => 0.0,
+            Solver::Yee => 0.5
+        }
+    }
+}
+
+pub struct StepContext<'a> {
+    pub dt: f32,
+    pub e0: f32,
+    pub m0: f32,
+    pub latice_density: [f32; 3],
+    pub boundary_conditions: &'a BoundaryConditions,
+    // Indexed by each node's `material_index`
+    pub materials: &'a [Material],
+    // Time the sources are sampled at this step
+    pub source_time: f32,
+    pub magnetic_source_time: f32,
+    // Time E is advanced to this step
+    pub field_time: f32,
+    // Time B is advanced to this step
+    pub b_field_time: f32,
+    pub sources: &'a SourceRegistry,
+    pub plane_waves: &'a [TfsfSurface]
+}
+
+
+// One real-valued copy of the fields along with the state needed to advance it
+pub struct FieldPart {
+    pub latice: Latice,
+    // Scratch latice the collocated solver writes the next step into
+    next: Option<Latice>,
+    cpml: Option<Cpml>,
+    dispersion: Option<Dispersion>
+}
+
+pub struct Fields {
+    pub real: FieldPart,
+    // Imaginary part of the fields, only carried when a Bloch-periodic boundary needs it
+    pub imaginary: Option<FieldPart>
+}
+impl Fields {
+    pub fn new(
+        latice: Latice,
+        solver: &Solver,
+        boundary_conditions: &BoundaryConditions,
+        materials: &[Material],
+        latice_density: [f32; 3],
+        dt: f32,
+        speed_of_light: f32
+    ) -> Self {
+        let part = |latice: Latice| FieldPart {
+            next: match solver {
+                Solver::Collocated => Some(latice.clone()),
+                Solver::Yee => None
+            },
+            cpml: Cpml::new(
+                boundary_conditions, latice.dimensions, latice_density, dt, speed_of_light, solver.e_derivative_offset()
+            ),
+            dispersion: Dispersion::new(&latice, materials),
+            latice
+        };
+        let imaginary = if boundary_conditions.iter().flatten().any(BoundaryCondition::is_complex) {
+            let mut imaginary = latice.clone();
+            for node in imaginary.cells.iter_mut() {
+                node.e = Field3Vec::default();
+                node.b = Field3Vec::default();
+            }
+            Some(part(imaginary))
+        } else {
+            None
+        };
+        Self {
+            real: part(latice),
+            imaginary
+        }
+    }
+
+    // Sources and plane waves only drive the real part of the fields
+    pub fn step(&mut self, solver: &Solver, context: &StepContext) {
+        match solver {
+            Solver::Collocated => {
+                match &mut self.imaginary {
+                    Some(imaginary) => {
+                        step_collocated(&mut self.real, Some(&imaginary.latice), 1.0, Some(context.sources), context);
+                        step_collocated(imaginary, Some(&self.real.latice), -1.0, None, context);
+                        // Both parts were computed from the previous step before either was swapped in
+                        swap_next(imaginary);
+                    }
+                    None => step_collocated(&mut self.real, None, 1.0, Some(context.sources), context)
+                }
+                // Both fields were advanced from the previous step's
+                let next = self.real.next.as_mut().unwrap();
+                let t = context.field_time - context.dt;
+                for plane_wave in context.plane_waves {
+                    plane_wave.correct_b(next, context.materials, t, context.dt, context.m0);
+                    plane_wave.correct_e(next, context.materials, t, context.dt, context.e0, context.m0);
+                }
+                swap_next(&mut self.real);
+            }
+            Solver::Yee => {
+                match &mut self.imaginary {
+                    Some(imaginary) => {
+                        update_b_yee(&mut self.real, Some(&imaginary.latice), 1.0, Some(context.sources), context);
+                        update_b_yee(imaginary, Some(&self.real.latice), -1.0, None, context);
+                        correct_b_yee(&mut self.real, context);
+                        update_e_yee(&mut self.real, Some(&imaginary.latice), 1.0, Some(context.sources), context);
+                        update_e_yee(imaginary, Some(&self.real.latice), -1.0, None, context);
+                        correct_e_yee(&mut self.real, context);
+                    }
+                    None => {
+                        update_b_yee(&mut self.real, None, 1.0, Some(context.sources), context);
+                        correct_b_yee(&mut self.real, context);
+                        update_e_yee(&mut self.real, None, 1.0, Some(context.sources), context);
+                        correct_e_yee(&mut self.real, context);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn swap_next(part: &mut FieldPart) {
+    if let Some(next) = part.next.as_mut() {
+        std::mem::swap(&mut part.latice, next);
+    }
+}
+
+
+fn step_collocated(
+    part: &mut FieldPart,
+    partner: Option<&Latice>,
+    part_sign: f32,
+    sources: Option<&SourceRegistry>,
+    context: &StepContext
+) {
+    let current = &part.latice;
+    let next = part.next.as_mut().unwrap();
+    let neighbors = Neighbors {
+        latice: current,
+        partner,
+        part_sign,
+        boundary_conditions: context.boundary_conditions,
+        materials: context.materials,
+        staggered: false
+    };
+    let [size_x, size_y, size_z] = current.dimensions;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let node = &current[(x, y, z)];
+
+                // Central differences, the boundary conditions supply the nodes past the faces
+                let [mut derivative_e_x, mut derivative_e_y, mut derivative_e_z] = [0, 1, 2].map(|axis|
+                    (neighbors.forward([x, y, z], axis, Field::E) - neighbors.backward([x, y, z], axis, Field::E)) * context.latice_density[axis] * 0.5
+                );
+                let [mut derivative_b_x, mut derivative_b_y, mut derivative_b_z] = [0, 1, 2].map(|axis|
+                    (neighbors.forward([x, y, z], axis, Field::H) - neighbors.backward([x, y, z], axis, Field::H)) * context.latice_density[axis] * 0.5
+                );
+
+                if let Some(cpml) = part.cpml.as_mut() {
+                    let cell = current.index((x, y, z));
+                    derivative_e_x = cpml.stretch_e(0, cell, x, derivative_e_x);
+                    derivative_e_y = cpml.stretch_e(1, cell, y, derivative_e_y);
+                    derivative_e_z = cpml.stretch_e(2, cell, z, derivative_e_z);
+                    derivative_b_x = cpml.stretch_b(0, cell, x, derivative_b_x);
+                    derivative_b_y = cpml.stretch_b(1, cell, y, derivative_b_y);
+                    derivative_b_z = cpml.stretch_b(2, cell, z, derivative_b_z);
+                }
+
+                let curl_e = curl(&derivative_e_x, &derivative_e_y, &derivative_e_z);
+                let curl_b = curl(&derivative_b_x, &derivative_b_y, &derivative_b_z);
+
+                let mut current_density = match sources {
+                    Some(sources) => source_current(sources, node, (x, y, z), context),
+                    None => Field3Vec::default()
+                };
+                let material = &context.materials[node.material_index];
+                if let Some(dispersion) = part.dispersion.as_mut() {
+                    let cell = current.index((x, y, z));
+                    current_density = current_density + dispersion.advance(cell, &material.poles, node.e, context.dt, context.e0);
+                }
+                let (e_decay, e_gain) = material.e_coefficients(context.dt, context.e0);
+                let (b_decay, b_gain) = material.b_coefficients(context.dt, context.m0);
+                let magnetic_current_density = match sources {
+                    Some(sources) => magnetic_source_current(sources, node, (x, y, z), context),
+                    None => Field3Vec::default()
+                };
+                let mut new_node = SpaceData {
+                    b: b_decay * node.b - b_gain * (curl_e + magnetic_current_density),
+                    e: e_decay * node.e + e_gain * ((curl_b / (context.e0 * context.m0)) - (current_density / context.e0)),
+                    ..*node
+                };
+                if let Some(sources) = sources {
+                    new_node.e = imposed_e(sources, &new_node, (x, y, z), context);
+                    new_node.b = imposed_b(sources, &new_node, (x, y, z), context);
+                }
+
+                next[(x, y, z)] = new_node;
+            }
+        }
+    }
+    boundary::enforce_e(next, context.boundary_conditions, false);
+    boundary::enforce_b(next, context.boundary_conditions, false);
+}
+
+
+/*
+ * Yee cell layout for node (x, y, z), in units of the latice spacing:
+ *   E_x at (x+1/2, y, z)      B_x at (x, y+1/2, z+1/2)
+ *   E_y at (x, y+1/2, z)      B_y at (x+1/2, y, z+1/2)
+ *   E_z at (x, y, z+1/2)      B_z at (x+1/2, y+1/2, z)
+ * so curl E only needs the node's forward neighbours and curl B only its backward ones.
+ */
+
+// B^(n+1/2) = B^(n-1/2) - dt * (curl E^n + M^n), less the magnetic conductivity's loss
+fn update_b_yee(
+    part: &mut FieldPart,
+    partner: Option<&Latice>,
+    part_sign: f32,
+    sources: Option<&SourceRegistry>,
+    context: &StepContext
+) {
+    let [size_x, size_y, size_z] = part.latice.dimensions;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let neighbors = Neighbors {
+                    latice: &part.latice,
+                    partner,
+                    part_sign,
+                    boundary_conditions: context.boundary_conditions,
+                    materials: context.materials,
+                    staggered: true
+                };
+                let node = part.latice[(x, y, z)];
+                let [mut derivative_e_x, mut derivative_e_y, mut derivative_e_z] = [0, 1, 2].map(|axis|
+                    (neighbors.forward([x, y, z], axis, Field::E) - node.e) * context.latice_density[axis]
+                );
+                if let Some(cpml) = part.cpml.as_mut() {
+                    let cell = part.latice.index((x, y, z));
+                    derivative_e_x = cpml.stretch_e(0, cell, x, derivative_e_x);
+                    derivative_e_y = cpml.stretch_e(1, cell, y, derivative_e_y);
+                    derivative_e_z = cpml.stretch_e(2, cell, z, derivative_e_z);
+                }
+
+                let curl_e = curl(&derivative_e_x, &derivative_e_y, &derivative_e_z);
+                let magnetic_current_density = match sources {
+                    Some(sources) => magnetic_source_current(sources, &node, (x, y, z), context),
+                    None => Field3Vec::default()
+                };
+                let (decay, gain) = context.materials[node.material_index].b_coefficients(context.dt, context.m0);
+                let updated = &mut part.latice[(x, y, z)];
+                updated.b = decay * node.b - gain * (curl_e + magnetic_current_density);
+                if let Some(sources) = sources {
+                    updated.b = imposed_b(sources, updated, (x, y, z), context);
+                }
+            }
+        }
+    }
+    boundary::enforce_b(&mut part.latice, context.boundary_conditions, true);
+}
+
+// B^(n+1/2) was advanced with E^n
+fn correct_b_yee(part: &mut FieldPart, context: &StepContext) {
+    for plane_wave in context.plane_waves {
+        plane_wave.correct_b(&mut part.latice, context.materials, context.field_time - context.dt, context.dt, context.m0);
+    }
+}
+
+// E^(n+1) was advanced with B^(n+1/2)
+fn correct_e_yee(part: &mut FieldPart, context: &StepContext) {
+    for plane_wave in context.plane_waves {
+        plane_wave.correct_e(&mut part.latice, context.materials, context.field_time - 0.5 * context.dt, context.dt, context.e0, context.m0);
+    }
+}
+
+// E^(n+1) = E^n + dt / permittivity * (curl (B / permeability)^(n+1/2) / (e0 * m0) - J^(n+1/2) / e0),
+// less the conductivity's loss. J includes the dispersive materials' polarization currents.
+fn update_e_yee(
+    part: &mut FieldPart,
+    partner: Option<&Latice>,
+    part_sign: f32,
+    sources: Option<&SourceRegistry>,
+    context: &StepContext
+) {
+    let [size_x, size_y, size_z] = part.latice.dimensions;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let neighbors = Neighbors {
+                    latice: &part.latice,
+                    partner,
+                    part_sign,
+                    boundary_conditions: context.boundary_conditions,
+                    materials: context.materials,
+                    staggered: true
+                };
+                let node = part.latice[(x, y, z)];
+                let [mut derivative_b_x, mut derivative_b_y, mut derivative_b_z] = [0, 1, 2].map(|axis|
+                    (Field::H.of(&node, context.materials) - neighbors.backward([x, y, z], axis, Field::H)) * context.latice_density[axis]
+                );
+                if let Some(cpml) = part.cpml.as_mut() {
+                    let cell = part.latice.index((x, y, z));
+                    derivative_b_x = cpml.stretch_b(0, cell, x, derivative_b_x);
+                    derivative_b_y = cpml.stretch_b(1, cell, y, derivative_b_y);
+                    derivative_b_z = cpml.stretch_b(2, cell, z, derivative_b_z);
+                }
+
+                let curl_b = curl(&derivative_b_x, &derivative_b_y, &derivative_b_z);
+                let mut current_density = match sources {
+                    Some(sources) => source_current(sources, &node, (x, y, z), context),
+                    None => Field3Vec::default()
+                };
+                let material = &context.materials[node.material_index];
+                if let Some(dispersion) = part.dispersion.as_mut() {
+                    let cell = part.latice.index((x, y, z));
+                    current_density = current_density + dispersion.advance(cell, &material.poles, node.e, context.dt, context.e0);
+                }
+                let (decay, gain) = material.e_coefficients(context.dt, context.e0);
+                let updated = &mut part.latice[(x, y, z)];
+                updated.e = decay * node.e + gain * (
+                    (curl_b / (context.e0 * context.m0)) - (current_density / context.e0)
+                );
+                if let Some(sources) = sources {
+                    updated.e = imposed_e(sources, updated, (x, y, z), context);
+                }
+            }
+        }
+    }
+    boundary::enforce_e(&mut part.latice, context.boundary_conditions, true);
+}
+
+fn source_current(sources: &SourceRegistry, node: &SpaceData, position: (usize, usize, usize), context: &StepContext) -> Field3Vec {
+    sources.get(node.object_index).current_density(&SourceContext {
+        t: context.source_time,
+        position,
+        e: node.e,
+        b: node.b
+    })
+}
+
+fn magnetic_source_current(sources: &SourceRegistry, node: &SpaceData, position: (usize, usize, usize), context: &StepContext) -> Field3Vec {
+    if node.object_index == 0 {
+        return Field3Vec::default();
+    }
+    sources.get(node.object_index).magnetic_current_density(&SourceContext {
+        t: context.magnetic_source_time,
+        position,
+        e: node.e,
+        b: node.b
+    })
+}
+
+// E of a freshly updated node, as held by the source driving it if any
+fn imposed_e(sources: &SourceRegistry, node: &SpaceData, position: (usize, usize, usize), context: &StepContext) -> Field3Vec {
+    if node.object_index == 0 {
+        return node.e;
+    }
+    sources.get(node.object_index).imposed_e(&SourceContext {
+        t: context.field_time,
+        position,
+        e: node.e,
+        b: node.b
+    }).unwrap_or(node.e)
+}
+
+// Counterpart of `imposed_e` for B
+fn imposed_b(sources: &SourceRegistry, node: &SpaceData, position: (usize, usize, usize), context: &StepContext) -> Field3Vec {
+    if node.object_index == 0 {
+        return node.b;
+    }
+    sources.get(node.object_index).imposed_b(&SourceContext {
+        t: context.b_field_time,
+        position,
+        e: node.e,
+        b: node.b
+    }).unwrap_or(node.b)
+}
+
+fn curl(derivative_x: &Field3Vec, derivative_y: &Field3Vec, derivative_z: &Field3Vec) -> Field3Vec {
+    Field3Vec { components: [
+        derivative_y.components[2] - derivative_z.components[1],
+        derivative_z.components[0] - derivative_x.components[2],
+        derivative_x.components[1] - derivative_y.components[0]
+    ] }
+}
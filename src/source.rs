@@ -0,0 +1,448 @@
+use std::{collections::HashMap, f32::consts::PI};
+use serde::Deserialize;
+use crate::{Field3Vec, Latice};
+use crate::waveform::Waveform;
+
+
+// What a source gets to know about the node it is driving
+pub struct SourceContext {
+    // Time the current density is sampled at
+    pub t: f32,
+    // Latice node being driven
+    pub position: (usize, usize, usize),
+    // Fields at the node as of the previous update, for sources that react to them
+    pub e: Field3Vec,
+    pub b: Field3Vec
+}
+
+// Extension point for excitations. Sources are shared by every node they drive, so they are
+// evaluated once per node and step and may be called from any thread.
+pub trait CurrentObject: Send + Sync {
+    fn current_density(&self, context: &SourceContext) -> Field3Vec;
+
+    // Fictitious magnetic current, draining B like J drains E
+    fn magnetic_current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+
+    // Lets a source hold E at its nodes outright, like a conductor or a voltage gap. Called after
+    // every update of E with the updated E in `context.e` and its time in `context.t`, returns the
+    // E to keep in its place.
+    fn imposed_e(&self, _context: &SourceContext) -> Option<Field3Vec> {
+        None
+    }
+
+    // Counterpart of `imposed_e` for B, with the updated B in `context.b`
+    fn imposed_b(&self, _context: &SourceContext) -> Option<Field3Vec> {
+        None
+    }
+
+    // Components of E and B the source drives at a node, those `Hard` overwrites
+    fn driven_components(&self, _position: (usize, usize, usize)) -> DrivenComponents {
+        DrivenComponents { e: [true; 3], b: [false; 3] }
+    }
+}
+
+pub struct DrivenComponents {
+    pub e: [bool; 3],
+    pub b: [bool; 3]
+}
+impl DrivenComponents {
+    fn along(e: Field3Vec, b: Field3Vec) -> Self {
+        Self { e: e.components.map(|component| component != 0.0), b: b.components.map(|component| component != 0.0) }
+    }
+}
+
+
+// Soft sources add their current to the update and let passing waves through, hard ones
+// overwrite the field they drive and reflect whatever reaches them
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceMode {
+    #[default]
+    Soft,
+    Hard
+}
+
+// Makes a source hard: the components it drives are set to its current density (or magnetic
+// current density, for B) rather than have it added to the update
+pub struct Hard(pub Box<dyn CurrentObject>);
+impl Hard {
+    fn overwrite(field: Field3Vec, value: Field3Vec, driven: [bool; 3]) -> Field3Vec {
+        Field3Vec { components: [0, 1, 2].map(|i| if driven[i] { value.components[i] } else { field.components[i] }) }
+    }
+}
+impl CurrentObject for Hard {
+    fn current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+
+    fn imposed_e(&self, context: &SourceContext) -> Option<Field3Vec> {
+        let driven = self.0.driven_components(context.position).e;
+        Some(Self::overwrite(context.e, self.0.current_density(context), driven))
+    }
+
+    fn imposed_b(&self, context: &SourceContext) -> Option<Field3Vec> {
+        let driven = self.0.driven_components(context.position).b;
+        Some(Self::overwrite(context.b, self.0.magnetic_current_density(context), driven))
+    }
+
+    fn driven_components(&self, position: (usize, usize, usize)) -> DrivenComponents {
+        self.0.driven_components(position)
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Wire {
+    pub waveform: Waveform,
+    pub amplitude: f32,
+    pub direction: Field3Vec,
+}
+impl CurrentObject for Wire {
+    fn current_density(&self, context: &SourceContext) -> Field3Vec {
+        self.amplitude * self.waveform.value(context.t) * self.direction
+    }
+
+    fn driven_components(&self, _position: (usize, usize, usize)) -> DrivenComponents {
+        DrivenComponents::along(self.direction, Field3Vec::default())
+    }
+}
+
+
+// A wire bent along a curve. Each node it drives carries the wire's length through it, as a
+// vector in units of latice spacing, so a straight stretch along an axis matches `Wire`.
+#[derive(Clone)]
+pub struct CurveWire {
+    pub waveform: Waveform,
+    pub amplitude: f32,
+    pub directions: HashMap<(usize, usize, usize), Field3Vec>
+}
+impl CurrentObject for CurveWire {
+    fn current_density(&self, context: &SourceContext) -> Field3Vec {
+        match self.directions.get(&context.position) {
+            Some(&direction) => self.amplitude * self.waveform.value(context.t) * direction,
+            None => Field3Vec::default()
+        }
+    }
+
+    fn driven_components(&self, position: (usize, usize, usize)) -> DrivenComponents {
+        DrivenComponents::along(self.directions.get(&position).copied().unwrap_or_default(), Field3Vec::default())
+    }
+}
+
+
+// Hertzian dipoles at a single node, `moment` being the current (or magnetic current) times the
+// length over the volume of a latice cell
+#[derive(Clone)]
+pub struct ElectricDipole {
+    pub moment: Field3Vec,
+    pub waveform: Waveform
+}
+impl CurrentObject for ElectricDipole {
+    fn current_density(&self, context: &SourceContext) -> Field3Vec {
+        self.waveform.value(context.t) * self.moment
+    }
+
+    fn driven_components(&self, _position: (usize, usize, usize)) -> DrivenComponents {
+        DrivenComponents::along(self.moment, Field3Vec::default())
+    }
+}
+
+#[derive(Clone)]
+pub struct MagneticDipole {
+    pub moment: Field3Vec,
+    pub waveform: Waveform
+}
+impl CurrentObject for MagneticDipole {
+    fn current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+
+    fn magnetic_current_density(&self, context: &SourceContext) -> Field3Vec {
+        self.waveform.value(context.t) * self.moment
+    }
+
+    fn driven_components(&self, _position: (usize, usize, usize)) -> DrivenComponents {
+        DrivenComponents::along(Field3Vec::default(), self.moment)
+    }
+}
+
+
+// Path of a wire, in the same units as the simulation side length
+pub enum Curve {
+    // Straight segments through `points`, back to the first one when closed
+    Polyline {
+        points: Vec<[f32; 3]>,
+        closed: bool
+    },
+    // Circle around an axis through `center`, counterclockwise seen from the positive axis
+    Loop {
+        center: [f32; 3],
+        radius: f32,
+        axis: usize
+    },
+    // Counterclockwise helix rising along an axis from `center`, `pitch` per turn
+    Helix {
+        center: [f32; 3],
+        radius: f32,
+        axis: usize,
+        pitch: f32,
+        turns: f32
+    }
+}
+impl Curve {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Curve::Polyline { ref points, .. } if points.len() < 2 => Err("a polyline needs at least 2 points".to_string()),
+            Curve::Loop { radius, .. } | Curve::Helix { radius, .. } if radius <= 0.0 => Err("radius must be positive".to_string()),
+            Curve::Helix { turns, .. } if turns <= 0.0 => Err("turns must be positive".to_string()),
+            _ => Ok(())
+        }
+    }
+
+    // Corners of the curve, round ones being cut into chords no longer than `spacing`
+    pub fn vertices(&self, spacing: f32) -> Vec<[f32; 3]> {
+        match *self {
+            Curve::Polyline { ref points, closed } => {
+                let mut vertices = points.clone();
+                if closed {
+                    vertices.push(points[0]);
+                }
+                vertices
+            }
+            Curve::Loop { center, radius, axis } => Self::helix(center, radius, axis, 0.0, 1.0, spacing),
+            Curve::Helix { center, radius, axis, pitch, turns } => Self::helix(center, radius, axis, pitch, turns, spacing)
+        }
+    }
+
+    fn helix(center: [f32; 3], radius: f32, axis: usize, pitch: f32, turns: f32, spacing: f32) -> Vec<[f32; 3]> {
+        // The two other axes in cyclic order, so that the angle winds counterclockwise around `axis`
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let count = ((2.0 * PI * radius * turns / spacing).ceil() as usize).max(8);
+        (0..=count).map(|i| {
+            let fraction = i as f32 / count as f32;
+            let angle = 2.0 * PI * turns * fraction;
+            let mut point = center;
+            point[u] += radius * angle.cos();
+            point[v] += radius * angle.sin();
+            point[axis] += pitch * turns * fraction;
+            point
+        }).collect()
+    }
+
+    // Nodes the curve passes by, each with the length of curve closest to it. Lengths are in
+    // latice spacings along each axis and in the order the curve first reaches the nodes.
+    pub fn rasterize(&self, dimensions: [usize; 3], latice_density: [f32; 3]) -> Vec<((usize, usize, usize), Field3Vec)> {
+        // Fine enough that every node along the way gets a few samples
+        let spacing = 0.25 / latice_density.iter().fold(0.0f32, |max, &density| max.max(density));
+        let mut order = Vec::new();
+        let mut lengths: HashMap<(usize, usize, usize), Field3Vec> = HashMap::new();
+        for segment in self.vertices(spacing).windows(2) {
+            let [start, end] = [segment[0], segment[1]];
+            let length = (0..3).map(|axis| (end[axis] - start[axis]).powi(2)).sum::<f32>().sqrt();
+            let pieces = (length / spacing).ceil().max(1.0) as usize;
+            let step = Field3Vec{ components: [0, 1, 2].map(|axis| (end[axis] - start[axis]) / pieces as f32 * latice_density[axis]) };
+            for piece in 0..pieces {
+                let fraction = (piece as f32 + 0.5) / pieces as f32;
+                let node = [0, 1, 2].map(|axis| {
+                    let position = (start[axis] + fraction * (end[axis] - start[axis])) * latice_density[axis];
+                    (position.round().max(0.0) as usize).min(dimensions[axis] - 1)
+                });
+                let position = (node[0], node[1], node[2]);
+                let total = lengths.entry(position).or_insert_with(|| {
+                    order.push(position);
+                    Field3Vec::default()
+                });
+                *total = *total + step;
+            }
+        }
+        order.into_iter().map(|position| (position, lengths[&position])).collect()
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Vaccum;
+impl CurrentObject for Vaccum {
+    fn current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+}
+
+
+// Every source of a simulation along with the nodes it drives. Entry 0 is the vacuum, which
+// drives every node no source has claimed, and each node's `object_index` points back into here.
+pub struct SourceRegistry {
+    sources: Vec<Box<dyn CurrentObject>>,
+    cells: Vec<Vec<(usize, usize, usize)>>
+}
+impl Default for SourceRegistry {
+    fn default() -> Self {
+        Self {
+            sources: vec![Box::new(Vaccum{})],
+            cells: vec![Vec::new()]
+        }
+    }
+}
+impl SourceRegistry {
+    // Records one source driving `cells` and tags them with its index in `latice`. Nodes can only be
+    // driven by one source: those an earlier source drove are taken from it without complaint and
+    // driven by this one alone.
+    pub fn register(&mut self, source: Box<dyn CurrentObject>, cells: &[(usize, usize, usize)], latice: &mut Latice) -> usize {
+        let object_index = self.sources.len();
+        for &position in cells {
+            let previous = latice[position].object_index;
+            self.cells[previous].retain(|&cell| cell != position);
+            latice[position].object_index = object_index;
+        }
+        self.sources.push(source);
+        self.cells.push(cells.to_vec());
+        object_index
+    }
+
+    pub fn get(&self, object_index: usize) -> &dyn CurrentObject {
+        self.sources[object_index].as_ref()
+    }
+
+    // Nodes driven by a source
+    pub fn cells(&self, object_index: usize) -> &[(usize, usize, usize)] {
+        &self.cells[object_index]
+    }
+
+    // Number of entries, the vacuum included
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    // Whether no source has been registered, the vacuum being all there is
+    pub fn is_empty(&self) -> bool {
+        self.sources.len() == 1
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimulationBuilder;
+    use crate::solver::Solver;
+
+    fn wire() -> Wire {
+        Wire { waveform: Waveform::Sine { angular_frequency: 3.0 }, amplitude: 2.0, direction: Field3Vec { components: [0.0, 0.0, 1.0] } }
+    }
+
+    #[test]
+    fn later_sources_take_over_shared_nodes() {
+        let mut registry = SourceRegistry::default();
+        let mut latice = Latice::new([4, 4, 4]);
+        assert!(registry.is_empty());
+        let first = registry.register(Box::new(wire()), &[(1, 1, 1), (1, 1, 2)], &mut latice);
+        let second = registry.register(Box::new(wire()), &[(1, 1, 2), (1, 1, 3)], &mut latice);
+        assert!(!registry.is_empty());
+        assert_eq!((first, second, registry.len()), (1, 2, 3));
+        assert_eq!(registry.cells(first), [(1, 1, 1)]);
+        assert_eq!(registry.cells(second), [(1, 1, 2), (1, 1, 3)]);
+        assert_eq!(latice[(1, 1, 2)].object_index, second);
+    }
+
+    #[test]
+    fn hard_overwrites_only_driven_components() {
+        let context = SourceContext {
+            t: 0.5,
+            position: (1, 1, 1),
+            e: Field3Vec { components: [1.0, 2.0, 3.0] },
+            b: Field3Vec { components: [4.0, 5.0, 6.0] }
+        };
+        let hard = Hard(Box::new(wire()));
+        assert_eq!(hard.imposed_e(&context).unwrap().components, [1.0, 2.0, 2.0 * 1.5f32.sin()]);
+        assert_eq!(hard.imposed_b(&context).unwrap().components, [4.0, 5.0, 6.0]);
+        assert_eq!(hard.current_density(&context).components, [0.0; 3]);
+    }
+
+    // A hard wire's nodes keep their Ez at the wire's current density step after step, while a step
+    // leaves their other components as it would without the wire
+    #[test]
+    fn hard_wire_holds_its_nodes() {
+        for solver in [Solver::Collocated, Solver::Yee] {
+            let simulation = |wire: Option<Wire>| {
+                let mut builder = SimulationBuilder::new([7, 7, 7], [10.0; 3], 0.02);
+                builder.solver(solver);
+                for position in [(2, 3, 3), (3, 3, 3), (4, 3, 3)] {
+                    builder.set_fields(position, Field3Vec { components: [0.5, -0.25, 1.0] }, Field3Vec { components: [0.1, 0.2, -0.3] });
+                }
+                let mut simulation = builder.build();
+                if let Some(wire) = wire {
+                    simulation.add_source(Box::new(Hard(Box::new(wire))), &[(3, 3, 2), (3, 3, 3), (3, 3, 4)]);
+                }
+                simulation.step();
+                simulation
+            };
+            let (mut driven, free) = (simulation(Some(wire())), simulation(None));
+            for z in 2..=4 {
+                assert_eq!(driven.e((3, 3, z)).components[..2], free.e((3, 3, z)).components[..2]);
+            }
+            assert_ne!(free.e((3, 3, 3)).components[2], driven.e((3, 3, 3)).components[2]);
+            for _ in 0..5 {
+                let t = driven.time();
+                for z in 2..=4 {
+                    assert_eq!(driven.e((3, 3, z)).components[2], 2.0 * (3.0 * t).sin());
+                }
+                driven.step();
+            }
+        }
+    }
+
+    // Sum of the lengths a curve's nodes get, in latice spacings
+    fn total_length(curve: &Curve) -> [f32; 3] {
+        curve.rasterize([40, 40, 40], [10.0; 3]).into_iter().fold(Field3Vec::default(), |total, (_, length)| total + length).components
+    }
+
+    fn assert_close(value: [f32; 3], expected: [f32; 3]) {
+        assert!((0..3).all(|axis| (value[axis] - expected[axis]).abs() < 1e-3), "{value:?} against {expected:?}");
+    }
+
+    #[test]
+    fn polyline_lengths_add_up() {
+        let straight = Curve::Polyline { points: vec![[0.5, 0.5, 0.5], [1.7, 1.1, 0.9]], closed: false };
+        assert_close(total_length(&straight), [12.0, 6.0, 4.0]);
+        // Every node of a straight segment carries current along it, adding up to its length
+        let nodes = straight.rasterize([40, 40, 40], [10.0; 3]);
+        let length: f32 = nodes.iter().map(|(_, length)| length.norm()).sum();
+        assert!((length - 14.0).abs() < 1e-3, "{length}");
+        for (_, length) in &nodes {
+            assert!(length.cross(Field3Vec { components: [6.0, 3.0, 2.0] }).norm() < 1e-3 * length.norm());
+        }
+        let bent = Curve::Polyline { points: vec![[0.5, 0.5, 0.5], [1.5, 0.5, 0.5], [1.5, 1.5, 0.5], [1.5, 1.5, 2.5]], closed: false };
+        assert_close(total_length(&bent), [10.0, 10.0, 20.0]);
+    }
+
+    #[test]
+    fn closed_curves_carry_no_net_current() {
+        let triangle = Curve::Polyline { points: vec![[0.5, 0.5, 0.5], [1.5, 0.5, 0.5], [1.0, 1.5, 1.0]], closed: true };
+        assert_close(total_length(&triangle), [0.0; 3]);
+        let circle = Curve::Loop { center: [2.0; 3], radius: 1.0, axis: 2 };
+        assert_close(total_length(&circle), [0.0; 3]);
+        // All the way around
+        let nodes = circle.rasterize([40, 40, 40], [10.0; 3]);
+        let length: f32 = nodes.iter().map(|(_, length)| length.norm()).sum();
+        assert!((length - 20.0 * PI).abs() < 0.1, "{length}");
+    }
+
+    #[test]
+    fn helix_pitch_and_turns() {
+        let helix = Curve::Helix { center: [2.0, 2.0, 0.5], radius: 0.8, axis: 2, pitch: 0.5, turns: 3.0 };
+        // Rising a pitch per turn around the axis, and carrying no net current around it
+        assert_close(total_length(&helix), [0.0, 0.0, 15.0]);
+        let vertices = helix.vertices(0.01);
+        let mut angle = 0.0;
+        for pair in vertices.windows(2) {
+            let [previous, next] = [pair[0], pair[1]].map(|point| (point[1] - 2.0).atan2(point[0] - 2.0));
+            angle += (next - previous + 3.0 * PI) % (2.0 * PI) - PI;
+            let point = pair[1];
+            assert!(((point[0] - 2.0).hypot(point[1] - 2.0) - 0.8).abs() < 1e-5);
+            assert!((point[2] - 0.5 - 0.5 * angle / (2.0 * PI)).abs() < 1e-4, "{point:?} after {angle}");
+        }
+        assert!((angle - 3.0 * 2.0 * PI).abs() < 1e-3, "{angle}");
+    }
+}
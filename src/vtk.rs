@@ -0,0 +1,264 @@
+use std::{io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+use crate::Simulation;
+use crate::format::{self, Component};
+use crate::output::{self, Field, OutputSink};
+use crate::xdmf::xml_escape;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VtkFormat {
+    // ImageData .vti files gathered by a .pvd collection holding their times, which are E's (B
+    // lags half a step behind on the Yee solver)
+    Xml,
+    // Numbered .vtk structured points files, which ParaView opens as a series
+    Legacy
+}
+
+
+// What is stored of one frame, over the nodes space culling keeps, x fastest
+struct Snapshot {
+    // Name, components per node and values of the selected fields, and of the magnitudes of the
+    // vectors among them
+    arrays: Vec<(String, usize, Vec<f32>)>,
+    material: Vec<i32>,
+    // 1 where a source drives the node
+    source: Vec<u8>
+}
+impl Snapshot {
+    fn new(simulation: &Simulation, space_culling_factor: u32, fields: &[Field]) -> Self {
+        let nodes = format::culled_nodes(simulation.latice(), space_culling_factor);
+        let mut arrays = Vec::new();
+        for field in fields {
+            let components = field.components();
+            arrays.push((
+                field.name().to_string(),
+                components.len(),
+                nodes.iter().flat_map(|node| components.iter().map(|component| component.of(node))).collect()
+            ));
+            if let Field::Vector(name, _) = field {
+                arrays.push((
+                    format!("{name}_magnitude"),
+                    1,
+                    nodes.iter().map(|node| components.iter().map(|component| component.of(node).powi(2)).sum::<f32>().sqrt()).collect()
+                ));
+            }
+        }
+        Self {
+            arrays,
+            material: nodes.iter().map(|node| node.material_index as i32).collect(),
+            source: nodes.iter().map(|node| (node.object_index != 0) as u8).collect()
+        }
+    }
+}
+
+
+// Snapshots of the selected components, the magnitudes of the vectors among them, the material of
+// every node and where sources drive the fields, one VTK file per frame
+pub struct VtkWriter {
+    path: PathBuf,
+    format: VtkFormat,
+    // Nodes stored along each axis, after space culling
+    dimensions: [usize; 3],
+    spacing: [f32; 3],
+    space_culling_factor: u32,
+    fields: Vec<Field>,
+    // Time and file name of every frame written so far
+    frames: Vec<(f32, String)>
+}
+impl VtkWriter {
+    // Frame files are named after `path`, e.g. fields.pvd gets fields_000010.vti for step 10. The
+    // legacy format has no collection file, `path` only naming the frames.
+    pub fn new(
+        path: &Path,
+        format: VtkFormat,
+        dimensions: [usize; 3],
+        spacing: [f32; 3],
+        space_culling_factor: u32,
+        components: &[Component]
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            format,
+            dimensions,
+            spacing,
+            space_culling_factor,
+            fields: Field::select(components),
+            frames: Vec::new()
+        }
+    }
+
+    // The first node kept is the last of the first `factor`
+    fn origin(&self) -> [f32; 3] {
+        let factor = self.space_culling_factor as f32;
+        self.spacing.map(|spacing| spacing * (factor - 1.0) / factor)
+    }
+
+    fn write_image_data(&self, writer: &mut impl Write, snapshot: &Snapshot) -> io::Result<()> {
+        let [size_x, size_y, size_z] = self.dimensions;
+        let extent = format!("0 {} 0 {} 0 {}", size_x - 1, size_y - 1, size_z - 1);
+        let [ox, oy, oz] = self.origin();
+        let [dx, dy, dz] = self.spacing;
+        // Arrays follow each other in the appended data, each after its length in bytes
+        let mut arrays: Vec<(&str, &str, usize, Vec<u8>)> = snapshot.arrays.iter().map(|(name, components, values)| {
+            (name.as_str(), "Float32", *components, values.iter().flat_map(|value| value.to_le_bytes()).collect())
+        }).collect();
+        arrays.push(("material", "Int32", 1, snapshot.material.iter().flat_map(|value| value.to_le_bytes()).collect()));
+        arrays.push(("source", "UInt8", 1, snapshot.source.clone()));
+        let vectors = arrays.iter().find(|(_, _, components, _)| *components == 3).map_or("", |(name, ..)| name);
+        let scalars = arrays.iter().find(|(_, _, components, _)| *components == 1).map_or("", |(name, ..)| name);
+        writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+        writeln!(writer, r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#)?;
+        writeln!(writer, r#"  <ImageData WholeExtent="{extent}" Origin="{ox} {oy} {oz}" Spacing="{dx} {dy} {dz}">"#)?;
+        writeln!(writer, r#"    <Piece Extent="{extent}">"#)?;
+        writeln!(writer, r#"      <PointData Vectors="{vectors}" Scalars="{scalars}">"#)?;
+        let mut offset = 0;
+        for (name, data_type, components, bytes) in &arrays {
+            writeln!(
+                writer,
+                r#"        <DataArray type="{data_type}" Name="{name}" NumberOfComponents="{components}" format="appended" offset="{offset}"/>"#
+            )?;
+            offset += 8 + bytes.len();
+        }
+        writeln!(writer, r#"      </PointData>"#)?;
+        writeln!(writer, r#"    </Piece>"#)?;
+        writeln!(writer, r#"  </ImageData>"#)?;
+        write!(writer, r#"  <AppendedData encoding="raw">"#)?;
+        writer.write_all(b"\n   _")?;
+        for (_, _, _, bytes) in &arrays {
+            writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, r#"  </AppendedData>"#)?;
+        writeln!(writer, r#"</VTKFile>"#)
+    }
+
+    // Binary legacy files are big endian
+    fn write_structured_points(&self, writer: &mut impl Write, snapshot: &Snapshot, step: u32, time: f32) -> io::Result<()> {
+        let [size_x, size_y, size_z] = self.dimensions;
+        let [ox, oy, oz] = self.origin();
+        let [dx, dy, dz] = self.spacing;
+        writeln!(writer, "# vtk DataFile Version 3.0")?;
+        writeln!(writer, "maximillion step {step}")?;
+        writeln!(writer, "BINARY")?;
+        writeln!(writer, "DATASET STRUCTURED_POINTS")?;
+        writeln!(writer, "DIMENSIONS {size_x} {size_y} {size_z}")?;
+        writeln!(writer, "ORIGIN {ox} {oy} {oz}")?;
+        writeln!(writer, "SPACING {dx} {dy} {dz}")?;
+        writeln!(writer, "FIELD FieldData 1")?;
+        writeln!(writer, "TIME 1 1 float")?;
+        writer.write_all(&time.to_be_bytes())?;
+        writeln!(writer)?;
+        writeln!(writer, "POINT_DATA {}", size_x * size_y * size_z)?;
+        for (name, components, values) in &snapshot.arrays {
+            if *components == 3 {
+                writeln!(writer, "VECTORS {name} float")?;
+            } else {
+                writeln!(writer, "SCALARS {name} float 1")?;
+                writeln!(writer, "LOOKUP_TABLE default")?;
+            }
+            writer.write_all(&values.iter().flat_map(|value| value.to_be_bytes()).collect::<Vec<_>>())?;
+            writeln!(writer)?;
+        }
+        writeln!(writer, "SCALARS material int 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        writer.write_all(&snapshot.material.iter().flat_map(|value| value.to_be_bytes()).collect::<Vec<_>>())?;
+        writeln!(writer)?;
+        writeln!(writer, "SCALARS source unsigned_char 1")?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        writer.write_all(&snapshot.source)?;
+        writeln!(writer)
+    }
+
+    fn write_collection(&self) -> io::Result<()> {
+        let mut writer = BufWriter::new(output::create(&self.path)?);
+        writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+        writeln!(writer, r#"<VTKFile type="Collection" version="1.0" byte_order="LittleEndian">"#)?;
+        writeln!(writer, r#"  <Collection>"#)?;
+        for (time, file_name) in &self.frames {
+            writeln!(writer, r#"    <DataSet timestep="{time}" part="0" file="{}"/>"#, xml_escape(file_name))?;
+        }
+        writeln!(writer, r#"  </Collection>"#)?;
+        writeln!(writer, r#"</VTKFile>"#)?;
+        writer.flush()
+    }
+}
+impl OutputSink for VtkWriter {
+    // Collections are rewritten after every frame so they always list every frame written
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        let snapshot = Snapshot::new(simulation, self.space_culling_factor, &self.fields);
+        let step = simulation.steps_taken();
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match self.format {
+            VtkFormat::Xml => format!("{stem}_{step:06}.vti"),
+            VtkFormat::Legacy => format!("{stem}_{step:06}.vtk")
+        };
+        let mut writer = BufWriter::new(output::create(&self.path.with_file_name(&file_name))?);
+        match self.format {
+            VtkFormat::Xml => self.write_image_data(&mut writer, &snapshot)?,
+            VtkFormat::Legacy => self.write_structured_points(&mut writer, &snapshot, step, simulation.time())?
+        }
+        writer.flush()?;
+        self.frames.push((simulation.time(), file_name));
+        match self.format {
+            VtkFormat::Xml => self.write_collection(),
+            VtkFormat::Legacy => Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writer(format: VtkFormat) -> VtkWriter {
+        VtkWriter::new(Path::new("fields.pvd"), format, [2, 2, 2], [0.5, 0.25, 1.0], 2, &[Component::Ex, Component::Ey, Component::Ez])
+    }
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            arrays: vec![("E".to_string(), 3, vec![1.0; 24]), ("E_magnitude".to_string(), 1, vec![3f32.sqrt(); 8])],
+            material: vec![0; 8],
+            source: vec![0; 8]
+        }
+    }
+
+    fn header(bytes: &[u8], end: &[u8]) -> String {
+        let length = bytes.windows(end.len()).position(|window| window == end).unwrap();
+        String::from_utf8(bytes[..length].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn image_data_header() {
+        let mut bytes = Vec::new();
+        writer(VtkFormat::Xml).write_image_data(&mut bytes, &snapshot()).unwrap();
+        let header = header(&bytes, b"  <AppendedData");
+        assert!(header.contains(r#"<ImageData WholeExtent="0 1 0 1 0 1" Origin="0.25 0.125 0.5" Spacing="0.5 0.25 1">"#));
+        assert!(header.contains(r#"<Piece Extent="0 1 0 1 0 1">"#));
+        assert!(header.contains(r#"<PointData Vectors="E" Scalars="E_magnitude">"#));
+        // Every array after the previous one and its 8 byte length
+        for array in [
+            r#"<DataArray type="Float32" Name="E" NumberOfComponents="3" format="appended" offset="0"/>"#,
+            r#"<DataArray type="Float32" Name="E_magnitude" NumberOfComponents="1" format="appended" offset="104"/>"#,
+            r#"<DataArray type="Int32" Name="material" NumberOfComponents="1" format="appended" offset="144"/>"#,
+            r#"<DataArray type="UInt8" Name="source" NumberOfComponents="1" format="appended" offset="184"/>"#
+        ] {
+            assert!(header.contains(array), "{array} is missing from\n{header}");
+        }
+        let appended = bytes.windows(5).position(|window| window == b"\n   _").unwrap() + 5;
+        assert_eq!(bytes.len() - appended, 184 + 8 + 8 + "\n  </AppendedData>\n</VTKFile>\n".len());
+    }
+
+    #[test]
+    fn structured_points_header() {
+        let mut bytes = Vec::new();
+        writer(VtkFormat::Legacy).write_structured_points(&mut bytes, &snapshot(), 10, 0.5).unwrap();
+        let header = header(&bytes, b"TIME 1 1 float\n");
+        assert_eq!(
+            header,
+            "# vtk DataFile Version 3.0\nmaximillion step 10\nBINARY\nDATASET STRUCTURED_POINTS\n\
+             DIMENSIONS 2 2 2\nORIGIN 0.25 0.125 0.5\nSPACING 0.5 0.25 1\nFIELD FieldData 1\n"
+        );
+    }
+}
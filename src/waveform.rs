@@ -0,0 +1,245 @@
+use std::{f32::consts::{E, PI}, fs, path::PathBuf};
+use serde::Deserialize;
+
+
+// Time dependence of a source, peaking at 1. Frequencies are angular and times in the manifest's
+// time units. Pulses centered at `delay` default to a delay that starts them from (nearly) zero.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Waveform {
+    // sin(angular_frequency t), switched on abruptly at t = 0
+    Sine {
+        angular_frequency: f32
+    },
+    // exp(-((t - delay) / width)^2)
+    Gaussian {
+        width: f32,
+        #[serde(default)]
+        delay: Option<f32>
+    },
+    // Time derivative of the Gaussian, free of any DC content
+    DifferentiatedGaussian {
+        width: f32,
+        #[serde(default)]
+        delay: Option<f32>
+    },
+    // Gaussian envelope on a sine carrier, its spectrum falls to 1/e at center_frequency +- bandwidth
+    ModulatedGaussian {
+        center_frequency: f32,
+        bandwidth: f32,
+        #[serde(default)]
+        delay: Option<f32>
+    },
+    // Mexican hat wavelet, its spectrum peaking at peak_frequency
+    Ricker {
+        peak_frequency: f32,
+        #[serde(default)]
+        delay: Option<f32>
+    },
+    // sin(angular_frequency t) brought up over ramp_time with a raised cosine
+    RampedSine {
+        angular_frequency: f32,
+        ramp_time: f32
+    },
+    // Linear interpolation between samples read from a CSV file of "time, value" rows, zero
+    // outside of them. The file is relative to the manifest.
+    Tabulated {
+        file: PathBuf,
+        #[serde(skip)]
+        samples: Vec<(f32, f32)>
+    }
+}
+impl Waveform {
+    pub fn value(&self, t: f32) -> f32 {
+        match self {
+            Waveform::Sine { angular_frequency } => (t * angular_frequency).sin(),
+            Waveform::Gaussian { width, delay } => {
+                let s = (t - delay.unwrap_or(3.0 * width)) / width;
+                (-s * s).exp()
+            }
+            Waveform::DifferentiatedGaussian { width, delay } => {
+                let s = (t - delay.unwrap_or(3.0 * width)) / width;
+                // Scaled so its extrema are +-1
+                -(2.0 * E).sqrt() * s * (-s * s).exp()
+            }
+            Waveform::ModulatedGaussian { center_frequency, bandwidth, delay } => {
+                let width = 2.0 / bandwidth;
+                let delay = delay.unwrap_or(3.0 * width);
+                let s = (t - delay) / width;
+                (-s * s).exp() * (center_frequency * (t - delay)).sin()
+            }
+            Waveform::Ricker { peak_frequency, delay } => {
+                let delay = delay.unwrap_or(3.0 * PI / peak_frequency);
+                let s = 0.5 * peak_frequency * (t - delay);
+                (1.0 - 2.0 * s * s) * (-s * s).exp()
+            }
+            Waveform::RampedSine { angular_frequency, ramp_time } => {
+                let ramp = if t >= *ramp_time { 1.0 } else { 0.5 * (1.0 - (PI * t.max(0.0) / ramp_time).cos()) };
+                ramp * (t * angular_frequency).sin()
+            }
+            Waveform::Tabulated { samples, .. } => {
+                let next = samples.partition_point(|&(time, _)| time <= t);
+                if next == 0 || next == samples.len() {
+                    // Past either end, unless exactly on the last sample
+                    return match samples.last() {
+                        Some(&(time, value)) if time == t => value,
+                        _ => 0.0
+                    };
+                }
+                let (t0, v0) = samples[next - 1];
+                let (t1, v1) = samples[next];
+                v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = |name: &str, value: f32| {
+            if value > 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(format!("{name} must be positive, found {value}"))
+            }
+        };
+        match self {
+            Waveform::Sine { .. } => Ok(()),
+            Waveform::Gaussian { width, .. } | Waveform::DifferentiatedGaussian { width, .. } => positive("width", *width),
+            Waveform::ModulatedGaussian { bandwidth, .. } => positive("bandwidth", *bandwidth),
+            Waveform::Ricker { peak_frequency, .. } => positive("peak_frequency", *peak_frequency),
+            Waveform::RampedSine { ramp_time, .. } => positive("ramp_time", *ramp_time),
+            Waveform::Tabulated { file, samples } if samples.is_empty() => Err(format!("the samples of {} were never loaded", file.display())),
+            Waveform::Tabulated { .. } => Ok(())
+        }
+    }
+
+    // Reads the samples of a tabulated waveform
+    pub fn load_samples(&mut self) -> Result<(), String> {
+        if let Waveform::Tabulated { file, samples } = self {
+            let contents = fs::read_to_string(&*file).map_err(|error| format!("cannot read {}: {error}", file.display()))?;
+            *samples = parse_samples(&contents).map_err(|message| format!("{}: {message}", file.display()))?;
+        }
+        Ok(())
+    }
+}
+
+// Rows of "time, value", skipping blank lines, "#" comments and a header row
+fn parse_samples(contents: &str) -> Result<Vec<(f32, f32)>, String> {
+    let mut samples: Vec<(f32, f32)> = Vec::new();
+    let mut header_allowed = true;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let parsed = match fields[..] {
+            [time, value] => time.parse::<f32>().ok().zip(value.parse::<f32>().ok()),
+            _ => None
+        };
+        let Some((time, value)) = parsed else {
+            if std::mem::take(&mut header_allowed) {
+                continue;
+            }
+            return Err(format!("line {}: expected \"time, value\", found \"{line}\"", i + 1));
+        };
+        header_allowed = false;
+        if samples.last().is_some_and(|&(last, _)| time <= last) {
+            return Err(format!("line {}: times must be increasing", i + 1));
+        }
+        samples.push((time, value));
+    }
+    if samples.is_empty() {
+        return Err("no samples".to_string());
+    }
+    Ok(samples)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-5, "{value} against {expected}");
+    }
+
+    #[test]
+    fn samples_skip_headers_and_comments() {
+        let samples = parse_samples("time, value\n# volts\n\n0, 0\n  1.5 ,2\n").unwrap();
+        assert_eq!(samples, [(0.0, 0.0), (1.5, 2.0)]);
+    }
+
+    #[test]
+    fn samples_must_be_increasing() {
+        assert_eq!(parse_samples("0, 0\n1, 1\n1, 2").unwrap_err(), "line 3: times must be increasing");
+        assert_eq!(parse_samples("0, 0\n-1, 1").unwrap_err(), "line 2: times must be increasing");
+    }
+
+    #[test]
+    fn malformed_samples() {
+        // Only the first row can be a header
+        assert_eq!(parse_samples("time, value\nvolts, amps\n0, 0").unwrap_err(), "line 2: expected \"time, value\", found \"volts, amps\"");
+        assert_eq!(parse_samples("0, 0\n1").unwrap_err(), "line 2: expected \"time, value\", found \"1\"");
+        assert_eq!(parse_samples("0, 0\n1, 2, 3").unwrap_err(), "line 2: expected \"time, value\", found \"1, 2, 3\"");
+        assert_eq!(parse_samples("time, value\n# nothing\n").unwrap_err(), "no samples");
+    }
+
+    #[test]
+    fn tabulated_interpolates_and_is_zero_outside() {
+        let waveform = Waveform::Tabulated { file: PathBuf::new(), samples: vec![(0.0, 0.0), (1.0, 2.0), (3.0, -2.0)] };
+        for (t, expected) in [(0.0, 0.0), (0.5, 1.0), (1.0, 2.0), (2.0, 0.0), (2.5, -1.0), (3.0, -2.0), (-0.1, 0.0), (3.1, 0.0)] {
+            assert_close(waveform.value(t), expected);
+        }
+    }
+
+    #[test]
+    fn gaussian_peak_and_width() {
+        let waveform = Waveform::Gaussian { width: 0.5, delay: None };
+        assert_close(waveform.value(1.5), 1.0);
+        assert_close(waveform.value(1.0), (-1f32).exp());
+        assert_close(waveform.value(2.0), (-1f32).exp());
+        assert!(waveform.value(0.0) < 1e-3);
+        assert_close(Waveform::Gaussian { width: 0.5, delay: Some(4.0) }.value(4.0), 1.0);
+    }
+
+    #[test]
+    fn differentiated_gaussian_extrema() {
+        let waveform = Waveform::DifferentiatedGaussian { width: 0.5, delay: None };
+        assert_close(waveform.value(1.5), 0.0);
+        assert_close(waveform.value(1.5 - 0.5 / 2f32.sqrt()), 1.0);
+        assert_close(waveform.value(1.5 + 0.5 / 2f32.sqrt()), -1.0);
+    }
+
+    #[test]
+    fn modulated_gaussian_envelope() {
+        // A width of 2 / bandwidth = 0.5, and the carrier at a crest one width from the delay
+        let center_frequency = 4.5 * PI / 0.5;
+        let waveform = Waveform::ModulatedGaussian { center_frequency, bandwidth: 4.0, delay: None };
+        assert_close(waveform.value(1.5), 0.0);
+        assert_close(waveform.value(2.0), (-1f32).exp());
+        assert_close(waveform.value(1.0), -(-1f32).exp());
+        let crest = waveform.value(1.5 + 0.5 * PI / center_frequency);
+        assert!(crest > 0.98, "{crest}");
+    }
+
+    #[test]
+    fn ricker_peak_and_width() {
+        let waveform = Waveform::Ricker { peak_frequency: 10.0, delay: None };
+        let delay = 3.0 * PI / 10.0;
+        assert_close(waveform.value(delay), 1.0);
+        // Crossing zero where 0.5 peak_frequency (t - delay) = 1 / sqrt(2)
+        assert_close(waveform.value(delay + 2f32.sqrt() / 10.0), 0.0);
+        assert_close(waveform.value(delay - 2f32.sqrt() / 10.0), 0.0);
+        assert!(waveform.value(0.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ramped_sine_reaches_full_amplitude() {
+        let waveform = Waveform::RampedSine { angular_frequency: 3.0, ramp_time: 2.0 };
+        assert_close(waveform.value(0.0), 0.0);
+        assert_close(waveform.value(1.0), 0.5 * 3f32.sin());
+        for t in [2.0, 2.5, 7.0] {
+            assert_close(waveform.value(t), (3.0 * t).sin());
+        }
+    }
+}
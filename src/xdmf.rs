@@ -0,0 +1,109 @@
+use std::{io::{self, BufWriter, Write}, path::{Path, PathBuf}};
+use crate::{Latice, Simulation};
+use crate::format::{self, Component};
+use crate::hdf5::{self, Dataset};
+use crate::output::{self, Field, OutputSink};
+
+
+// Snapshots of the selected components, one HDF5 file per frame next to an XDMF file describing
+// the grid and the time of every frame, the one ParaView and VisIt open. The time is E's, B
+// lagging half a step behind it on the Yee solver.
+pub struct XdmfWriter {
+    path: PathBuf,
+    // Nodes stored along each axis, after space culling
+    dimensions: [usize; 3],
+    spacing: [f32; 3],
+    space_culling_factor: u32,
+    fields: Vec<Field>,
+    // Step, time and file name of every frame written so far
+    frames: Vec<(u32, f32, String)>
+}
+impl XdmfWriter {
+    // Frame files are named after `path`, e.g. fields.xmf gets fields_000010.h5 for step 10
+    pub fn new(path: &Path, dimensions: [usize; 3], spacing: [f32; 3], space_culling_factor: u32, components: &[Component]) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            dimensions,
+            spacing,
+            space_culling_factor,
+            fields: Field::select(components),
+            frames: Vec::new()
+        }
+    }
+
+    // Writes the nodes space culling keeps, then rewrites the XDMF file so it always lists every
+    // frame written
+    pub fn write_frame(&mut self, step: u32, time: f32, latice: &Latice) -> io::Result<()> {
+        let nodes = format::culled_nodes(latice, self.space_culling_factor);
+        let values: Vec<Vec<f32>> = self.fields.iter().map(|field| {
+            nodes.iter().flat_map(|node| field.components().iter().map(|component| component.of(node))).collect()
+        }).collect();
+        let [size_x, size_y, size_z] = self.dimensions;
+        let datasets: Vec<Dataset> = self.fields.iter().zip(&values).map(|(field, values)| {
+            let mut shape = vec![size_z, size_y, size_x];
+            if let Field::Vector(..) = field {
+                shape.push(3);
+            }
+            Dataset { name: field.name(), shape, values }
+        }).collect();
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = format!("{stem}_{step:06}.h5");
+        let mut writer = BufWriter::new(output::create(&self.path.with_file_name(&file_name))?);
+        hdf5::write_file(&mut writer, &datasets)?;
+        writer.flush()?;
+        self.frames.push((step, time, file_name));
+        self.write_description()
+    }
+
+    fn write_description(&self) -> io::Result<()> {
+        let mut writer = BufWriter::new(output::create(&self.path)?);
+        let [size_x, size_y, size_z] = self.dimensions;
+        let [dx, dy, dz] = self.spacing;
+        // The first node kept is the last of the first `factor`
+        let factor = self.space_culling_factor as f32;
+        let [ox, oy, oz] = self.spacing.map(|spacing| spacing * (factor - 1.0) / factor);
+        writeln!(writer, r#"<?xml version="1.0" ?>"#)?;
+        writeln!(writer, r#"<!DOCTYPE Xdmf SYSTEM "Xdmf.dtd" []>"#)?;
+        writeln!(writer, r#"<Xdmf Version="3.0">"#)?;
+        writeln!(writer, r#"  <Domain>"#)?;
+        writeln!(writer, r#"    <Grid Name="fields" GridType="Collection" CollectionType="Temporal">"#)?;
+        for (step, time, file_name) in &self.frames {
+            writeln!(writer, r#"      <Grid Name="step {step}" GridType="Uniform">"#)?;
+            writeln!(writer, r#"        <Time Value="{time}"/>"#)?;
+            // Dimensions and geometry go slowest axis first
+            writeln!(writer, r#"        <Topology TopologyType="3DCoRectMesh" Dimensions="{size_z} {size_y} {size_x}"/>"#)?;
+            writeln!(writer, r#"        <Geometry GeometryType="ORIGIN_DXDYDZ">"#)?;
+            writeln!(writer, r#"          <DataItem Dimensions="3" NumberType="Float" Precision="4" Format="XML">{oz} {oy} {ox}</DataItem>"#)?;
+            writeln!(writer, r#"          <DataItem Dimensions="3" NumberType="Float" Precision="4" Format="XML">{dz} {dy} {dx}</DataItem>"#)?;
+            writeln!(writer, r#"        </Geometry>"#)?;
+            for field in &self.fields {
+                let name = field.name();
+                let (attribute_type, dimensions) = match field {
+                    Field::Vector(..) => ("Vector", format!("{size_z} {size_y} {size_x} 3")),
+                    Field::Scalar(_) => ("Scalar", format!("{size_z} {size_y} {size_x}"))
+                };
+                writeln!(writer, r#"        <Attribute Name="{name}" AttributeType="{attribute_type}" Center="Node">"#)?;
+                writeln!(
+                    writer,
+                    r#"          <DataItem Dimensions="{dimensions}" NumberType="Float" Precision="4" Format="HDF">{}:/{name}</DataItem>"#,
+                    xml_escape(file_name)
+                )?;
+                writeln!(writer, r#"        </Attribute>"#)?;
+            }
+            writeln!(writer, r#"      </Grid>"#)?;
+        }
+        writeln!(writer, r#"    </Grid>"#)?;
+        writeln!(writer, r#"  </Domain>"#)?;
+        writeln!(writer, r#"</Xdmf>"#)?;
+        writer.flush()
+    }
+}
+impl OutputSink for XdmfWriter {
+    fn write(&mut self, simulation: &Simulation) -> io::Result<()> {
+        self.write_frame(simulation.steps_taken(), simulation.time(), simulation.latice())
+    }
+}
+
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
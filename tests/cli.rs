@@ -0,0 +1,147 @@
+use std::{env, fs, fs::File, io::BufReader, path::{Path, PathBuf}, process::{self, Command, Output}};
+use maximillion::format::{FrameReader, Header};
+
+
+// Small PEC cavity that simulates in a moment
+const CAVITY: &str = r#"{
+    "constants": {
+        "e0": 1,
+        "m0": 1,
+        "dt": 0.02,
+        "steps": 4,
+        "time_culling_factor": 1,
+        "space_culling_factor": 1,
+        "boundary_condition": "pec",
+        "solver": "yee",
+        "latice_density": 5,
+        "simulation_side_length": 1
+    },
+    "objects": [
+        {"type": "point", "location": [2, 2, 2], "E": [0, 0, 1], "B": [0, 0, 0]}
+    ]
+}"#;
+
+// Directory of its own for each test, removed when the test is done with it
+struct Scratch(PathBuf);
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("maximillion_cli_{}_{name}", process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    // Writes a file into the directory, returns its path
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(manifest: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_maximillion"))
+        .arg(manifest)
+        .args(args)
+        .arg("--quiet")
+        .output()
+        .unwrap()
+}
+
+fn assert_exit(output: &Output, code: i32, message: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(code), "stderr: {stderr}");
+    assert!(stderr.contains(message), "expected {message:?} in stderr: {stderr}");
+}
+
+// Steps and times of the frames in a binary field file, with its header
+fn frames(path: &Path) -> (Vec<(u32, f32)>, Header) {
+    let reader = FrameReader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let header = reader.header().clone();
+    (reader.map(|frame| frame.map(|frame| (frame.step, frame.time)).unwrap()).collect(), header)
+}
+
+#[test]
+fn manifest_errors_have_their_own_exit_codes() {
+    let scratch = Scratch::new("manifest_errors");
+    assert_exit(&run(&scratch.0.join("missing.json"), &[]), 3, "cannot read manifest");
+    assert_exit(&run(&scratch.file("syntax.json", "{\"constants\": "), &[]), 4, "syntax.json: ");
+    let schema = scratch.file("schema.json", &CAVITY.replace("\"steps\": 4", "\"steps\": \"four\""));
+    assert_exit(&run(&schema, &[]), 5, "constants.steps");
+    let invalid = scratch.file("invalid.json", &CAVITY.replace("\"dt\": 0.02", "\"dt\": 0"));
+    assert_exit(&run(&invalid, &[]), 6, "constants.dt");
+}
+
+#[test]
+fn unwritable_output_exits_with_7() {
+    let scratch = Scratch::new("unwritable_output");
+    let manifest = scratch.file("cavity.json", CAVITY);
+    let output = scratch.0.join("missing").join("fields.max");
+    assert_exit(&run(&manifest, &["--format", "binary", "--output", output.to_str().unwrap()]), 7, "Failed to write the simulation output");
+}
+
+#[test]
+fn validate_only_checks_without_simulating() {
+    let scratch = Scratch::new("validate_only");
+    let manifest = scratch.file("cavity.json", CAVITY);
+    let output = scratch.0.join("fields.max");
+    let checked = Command::new(env!("CARGO_BIN_EXE_maximillion"))
+        .arg(&manifest)
+        .args(["--validate-only", "--format", "binary", "--output", output.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_exit(&checked, 0, "manifest is valid");
+    assert!(checked.stdout.is_empty());
+    assert!(!output.exists());
+
+    let invalid = scratch.file("invalid.json", &CAVITY.replace("\"latice_density\": 5", "\"latice_density\": -5"));
+    assert_exit(&run(&invalid, &["--validate-only"]), 6, "constants.latice_density");
+}
+
+#[test]
+fn format_and_output_conflict_with_listed_outputs() {
+    let scratch = Scratch::new("conflicts");
+    let listed = scratch.file("listed.json", &CAVITY.replace(
+        "\"objects\"", "\"outputs\": [{\"type\": \"binary\", \"file\": \"fields.max\"}],\n    \"objects\""
+    ));
+    assert_exit(&run(&listed, &["--format", "binary"]), 2, "--format can't be used with a manifest listing its outputs");
+    let output = scratch.0.join("elsewhere.max");
+    assert_exit(&run(&listed, &["--output", output.to_str().unwrap()]), 2, "--output isn't used");
+    assert!(!scratch.0.join("fields.max").exists() && !output.exists());
+
+    let unlisted = scratch.file("unlisted.json", CAVITY);
+    assert_exit(&run(&unlisted, &["--format", "vtk"]), 2, "which must be given");
+}
+
+#[test]
+fn steps_and_dt_override_the_manifest() {
+    let scratch = Scratch::new("overrides");
+    let manifest = scratch.file("cavity.json", CAVITY);
+    let output = scratch.0.join("fields.max");
+    let output = output.to_str().unwrap();
+
+    assert_exit(&run(&manifest, &["--format", "binary", "--output", output]), 0, "");
+    let (written, header) = frames(Path::new(output));
+    assert_eq!(written.iter().map(|(step, _)| *step).collect::<Vec<_>>(), [0, 1, 2, 3]);
+    assert_eq!((header.frame_dt, header.b_time_offset), (0.02, -0.01));
+    assert_eq!(header.manifest, CAVITY);
+
+    assert_exit(&run(&manifest, &["--format", "binary", "--output", output, "--steps", "7", "--dt", "0.01"]), 0, "");
+    let (written, header) = frames(Path::new(output));
+    assert_eq!(written.iter().map(|(step, _)| *step).collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5, 6]);
+    assert_eq!((header.frame_dt, header.b_time_offset), (0.01, -0.005));
+    let (_, time) = written[6];
+    assert!((time - 0.06).abs() < 1e-6, "last frame at {time}");
+    // The copy of the manifest is of what was simulated
+    let copy: serde_json::Value = serde_json::from_str(&header.manifest).unwrap();
+    assert_eq!(copy["constants"]["steps"], 7);
+    assert_eq!(copy["constants"]["dt"], 0.01);
+    assert_eq!(copy["objects"], serde_json::from_str::<serde_json::Value>(CAVITY).unwrap()["objects"]);
+
+    // Overrides are checked like the manifest's own values
+    assert_exit(&run(&manifest, &["--dt=0"]), 6, "constants.dt");
+}
@@ -0,0 +1,7 @@
+use maximillion::Simulation;
+
+
+// Electromagnetic energy over the latice, in units of the spacing cubed
+pub fn energy(simulation: &Simulation, e0: f32, m0: f32) -> f32 {
+    simulation.latice().cells.iter().map(|node| 0.5 * (e0 * node.e.dot(node.e) + node.b.dot(node.b) / m0)).sum()
+}
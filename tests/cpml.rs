@@ -0,0 +1,47 @@
+use maximillion::Simulation;
+use maximillion::manifest::Manifest;
+
+mod common;
+
+
+// A Ricker pulse from a dipole in the middle of the latice, free of any DC content so nothing
+// is left behind once it has gone
+fn pulse(boundary_condition: &str) -> Manifest {
+    serde_json::from_str(&format!(r#"{{
+        "constants": {{
+            "e0": 1,
+            "m0": 1,
+            "dt": 0.025,
+            "steps": 1,
+            "time_culling_factor": 1,
+            "space_culling_factor": 1,
+            "boundary_condition": {boundary_condition},
+            "solver": "yee",
+            "latice_density": 16,
+            "simulation_side_length": 1.5
+        }},
+        "objects": [
+            {{"type": "electric_dipole", "location": 0.75, "orientation": [0, 0, 1], "amplitude": 1, "waveform": {{"type": "ricker", "peak_frequency": 12}}}}
+        ]
+    }}"#)).unwrap()
+}
+
+// Energy left once the pulse has had time to cross the latice, against the most there was
+fn remaining_energy(boundary_condition: &str) -> f32 {
+    let mut simulation = Simulation::from_manifest(&pulse(boundary_condition)).unwrap();
+    let mut peak: f32 = 0.0;
+    for _ in 0..320 {
+        simulation.step();
+        peak = peak.max(common::energy(&simulation, 1.0, 1.0));
+    }
+    common::energy(&simulation, 1.0, 1.0) / peak
+}
+
+#[test]
+fn cpml_absorbs_outgoing_waves() {
+    let absorbed = remaining_energy(r#"{"type": "cpml", "thickness": 6}"#);
+    let clipped = remaining_energy(r#""clip""#);
+    assert!(absorbed < 1e-3, "{absorbed} of the energy is left with the CPML");
+    // Clipped faces reflect much of the pulse back in
+    assert!(clipped > 0.1, "{clipped} of the energy is left with clipped faces");
+}
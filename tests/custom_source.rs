@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+use maximillion::{CurrentObject, Field3Vec, Simulation, SimulationBuilder, SourceContext};
+use maximillion::manifest::Manifest;
+use maximillion::solver::Solver;
+
+
+// Node and time of every sample taken
+type Calls = Arc<Mutex<Vec<((usize, usize, usize), f32)>>>;
+
+// Steady current along z, which notes down every node and time it is sampled at
+struct Recorded {
+    current: f32,
+    calls: Calls
+}
+impl CurrentObject for Recorded {
+    fn current_density(&self, context: &SourceContext) -> Field3Vec {
+        self.calls.lock().unwrap().push((context.position, context.t));
+        Field3Vec { components: [0.0, 0.0, self.current] }
+    }
+}
+
+// Holds Ex at its nodes, like a voltage gap, whatever the fields around it do
+struct Clamp(f32);
+impl CurrentObject for Clamp {
+    fn current_density(&self, _context: &SourceContext) -> Field3Vec {
+        Field3Vec::default()
+    }
+
+    fn imposed_e(&self, context: &SourceContext) -> Option<Field3Vec> {
+        let [_, y, z] = context.e.components;
+        Some(Field3Vec { components: [self.0, y, z] })
+    }
+}
+
+#[test]
+fn builder_drives_a_custom_source() {
+    let dt = 0.02;
+    let mut builder = SimulationBuilder::new([21, 21, 21], [20.0; 3], dt);
+    builder.solver(Solver::Yee);
+    let calls = Calls::default();
+    let cells = [(10, 10, 9), (10, 10, 10)];
+    let index = builder.add_source(Box::new(Recorded { current: 2.0, calls: calls.clone() }), &cells);
+    let mut simulation = builder.build();
+    assert_eq!(simulation.sources().cells(index), cells);
+
+    // Nothing but the source moves E from zero, so the first step is -J dt / e0 on its nodes only
+    simulation.step();
+    for position in cells {
+        let e = simulation.e(position).components;
+        assert!(e[0] == 0.0 && e[1] == 0.0 && (e[2] + 2.0 * dt).abs() < 1e-6, "E {e:?} at {position:?}");
+    }
+    assert_eq!(simulation.e((10, 10, 11)).components, [0.0; 3]);
+    assert_eq!(simulation.e((12, 10, 10)).components, [0.0; 3]);
+
+    // The current curls B around it and the fields spread away from the wire
+    simulation.run(19);
+    assert!(simulation.b((10, 10, 10)).norm() > 1e-4);
+    assert!(simulation.e((13, 10, 10)).norm() > 1e-6);
+
+    // Sampled once per node and step, one step apart
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.len(), 20 * cells.len());
+    let start = calls[0].1;
+    for (step, calls) in calls.chunks(cells.len()).enumerate() {
+        let mut positions: Vec<_> = calls.iter().map(|(position, _)| *position).collect();
+        positions.sort();
+        assert_eq!(positions, cells);
+        let t = start + step as f32 * dt;
+        assert!(calls.iter().all(|&(_, time)| (time - t).abs() < 1e-5), "times {calls:?} at step {step}");
+    }
+}
+
+#[test]
+fn simulation_takes_a_source_once_running() {
+    let manifest: Manifest = serde_json::from_str(r#"{
+        "constants": {
+            "e0": 1,
+            "m0": 1,
+            "dt": 0.02,
+            "steps": 1,
+            "time_culling_factor": 1,
+            "space_culling_factor": 1,
+            "boundary_condition": "pec",
+            "solver": "yee",
+            "latice_density": 20,
+            "simulation_side_length": 1
+        },
+        "objects": [
+            {"type": "point", "location": [5, 5, 5], "E": [0, 0, 1], "B": [0, 0, 0]}
+        ]
+    }"#).unwrap();
+    let mut simulation = Simulation::from_manifest(&manifest).unwrap();
+    simulation.run(10);
+    let before = simulation.e((15, 10, 10)).components[0];
+
+    let sources = simulation.sources().len();
+    let index = simulation.add_source(Box::new(Clamp(0.7)), &[(15, 10, 10)]);
+    assert_eq!(index, sources);
+    assert_eq!(simulation.sources().len(), sources + 1);
+    for _ in 0..30 {
+        simulation.step();
+        assert_eq!(simulation.e((15, 10, 10)).components[0], 0.7);
+    }
+    assert_ne!(before, 0.7);
+    // The held field pushes out into its neighbours
+    assert!(simulation.b((15, 10, 10)).norm() > 1e-3);
+    assert!(simulation.e((15, 10, 13)).components[0].abs() > 1e-3);
+}
@@ -0,0 +1,53 @@
+use maximillion::Simulation;
+use maximillion::manifest::Manifest;
+
+
+// A Gaussian pulse travelling along x through a latice periodic along y and z, so a plane wave,
+// towards the slab from x = 1.5 to 1.7 when there is one
+fn pulse(slab: bool) -> Manifest {
+    let mut objects: Vec<String> = (0..60).map(|x| {
+        let e = (-((x as f32 / 40.0 - 0.75) / 0.15).powi(2)).exp();
+        format!(r#"{{"type": "plane", "axis": "x", "location": {x}, "E": [0, 0, {e}], "B": [0, {}, 0]}}"#, -e)
+    }).collect();
+    if slab {
+        objects.push(r#"{"type": "slab", "axis": "x", "from": 1.5, "to": 1.7, "material": "metal"}"#.to_string());
+    }
+    serde_json::from_str(&format!(r#"{{
+        "constants": {{
+            "e0": 1,
+            "m0": 1,
+            "dt": 0.01,
+            "steps": 1,
+            "time_culling_factor": 1,
+            "space_culling_factor": 1,
+            "boundary_condition": {{"x": {{"type": "cpml", "thickness": 10}}, "y": "periodic", "z": "periodic"}},
+            "solver": "yee",
+            "latice_density": 40,
+            "simulation_side_length": [3, 0.1, 0.1]
+        }},
+        "materials": {{
+            "metal": {{"poles": [{{"type": "drude", "plasma_frequency": 60, "collision_frequency": 1}}]}}
+        }},
+        "objects": [{}]
+    }}"#, objects.join(", "))).unwrap()
+}
+
+// Largest Ez seen at x = 2.4, past the slab, while the pulse goes by
+fn transmitted(slab: bool) -> f32 {
+    let mut simulation = Simulation::from_manifest(&pulse(slab)).unwrap();
+    let mut largest: f32 = 0.0;
+    for _ in 0..250 {
+        simulation.step();
+        largest = largest.max(simulation.e((96, 1, 1)).components[2].abs());
+    }
+    largest
+}
+
+#[test]
+fn drude_slab_screens_a_plane_wave() {
+    let unscreened = transmitted(false);
+    let screened = transmitted(true);
+    assert!(unscreened > 0.5, "the pulse never arrived, {unscreened}");
+    // The pulse is far below the plasma frequency, a skin depth of 1/60 being under a node
+    assert!(screened < 0.01 * unscreened, "{screened} got through the slab against {unscreened} without it");
+}
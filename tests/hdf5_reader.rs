@@ -0,0 +1,54 @@
+use std::{env, fs, process::Command};
+use maximillion::hdf5::{self, Dataset};
+use serde_json::Value;
+
+
+// Lists every dataset of the file with its shape, type and values, as JSON
+const LIST_DATASETS: &str = r#"
+import h5py, json, sys
+with h5py.File(sys.argv[1], "r") as file:
+    print(json.dumps({
+        name: {"shape": list(dataset.shape), "dtype": dataset.dtype.str, "values": dataset[()].ravel().tolist()}
+        for name, dataset in file.items()
+    }))
+"#;
+
+// Reads the files back through libhdf5, by way of h5py. Skipped where h5py isn't installed, unless
+// MAXIMILLION_REQUIRE_HDF5_READER is set, as it should be wherever h5py is meant to be.
+#[test]
+fn libhdf5_reads_the_files_back() {
+    let python = env::var("PYTHON").unwrap_or("python3".to_string());
+    let available = Command::new(&python).args(["-c", "import h5py"]).output().is_ok_and(|output| output.status.success());
+    if !available {
+        assert!(env::var_os("MAXIMILLION_REQUIRE_HDF5_READER").is_none(), "h5py cannot be imported by {python}");
+        eprintln!("skipped, h5py cannot be imported by {python}");
+        return;
+    }
+
+    let e: Vec<f32> = (0..2 * 3 * 4 * 3).map(|i| i as f32 * 0.25 - 7.0).collect();
+    let bz = [1.0, -2.0, 3.5, f32::MIN_POSITIVE, f32::MAX, -0.0];
+    // More than a default symbol table node holds
+    let names: Vec<String> = (0..12).map(|i| format!("extra_{i}")).collect();
+    let mut datasets = vec![
+        Dataset { name: "E", shape: vec![2, 3, 4, 3], values: &e },
+        Dataset { name: "Bz", shape: vec![1, 2, 3], values: &bz }
+    ];
+    datasets.extend(names.iter().map(|name| Dataset { name, shape: vec![1], values: &bz[..1] }));
+    let path = env::temp_dir().join(format!("maximillion_hdf5_reader_{}.h5", std::process::id()));
+    let mut file = Vec::new();
+    hdf5::write_file(&mut file, &datasets).unwrap();
+    fs::write(&path, file).unwrap();
+
+    let output = Command::new(&python).args(["-c", LIST_DATASETS]).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let read: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(read.as_object().unwrap().len(), datasets.len());
+    for dataset in &datasets {
+        let read = &read[dataset.name];
+        assert_eq!(read["dtype"], "<f4", "{}", dataset.name);
+        assert_eq!(read["shape"], serde_json::json!(dataset.shape), "{}", dataset.name);
+        let values: Vec<f32> = read["values"].as_array().unwrap().iter().map(|value| value.as_f64().unwrap() as f32).collect();
+        assert_eq!(values.iter().map(|value| value.to_bits()).collect::<Vec<_>>(), dataset.values.iter().map(|value| value.to_bits()).collect::<Vec<_>>());
+    }
+}
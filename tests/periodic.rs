@@ -0,0 +1,90 @@
+use std::f32::consts::FRAC_PI_2;
+use maximillion::{Latice, Simulation};
+use maximillion::manifest::Manifest;
+
+
+const DENSITY: usize = 20;
+
+// A latice `periods` units long along x, periodic along y and z, with Ez and By set on planes
+// normal to x: `(location, Ez, By)`
+fn manifest(periods: usize, boundary_condition: &str, planes: &[(usize, f32, f32)]) -> Manifest {
+    let objects: Vec<String> = planes.iter().map(|(x, e, b)| {
+        format!(r#"{{"type": "plane", "axis": "x", "location": {x}, "E": [0, 0, {e}], "B": [0, {b}, 0]}}"#)
+    }).collect();
+    serde_json::from_str(&format!(r#"{{
+        "constants": {{
+            "e0": 1,
+            "m0": 1,
+            "dt": 0.02,
+            "steps": 1,
+            "time_culling_factor": 1,
+            "space_culling_factor": 1,
+            "boundary_condition": {{"x": {boundary_condition}, "y": "periodic", "z": "periodic"}},
+            "solver": "yee",
+            "latice_density": {DENSITY},
+            "simulation_side_length": [{periods}, 0.15, 0.15]
+        }},
+        "objects": [{}]
+    }}"#, objects.join(", "))).unwrap()
+}
+
+// A Gaussian pulse `shift` nodes along from the middle of the first period, wrapping around it,
+// travelling along +x when `travelling`
+fn pulse(shift: usize, scale: f32, travelling: bool) -> Vec<(usize, f32, f32)> {
+    (0..DENSITY).map(|x| {
+        let offset = (x as f32 - DENSITY as f32 / 2.0) / 3.0;
+        let e = scale * (-offset * offset).exp();
+        ((x + shift) % DENSITY, e, if travelling { -e } else { 0.0 })
+    }).collect()
+}
+
+fn run(manifest: &Manifest, steps: u32) -> Simulation {
+    let mut simulation = Simulation::from_manifest(manifest).unwrap();
+    simulation.run(steps);
+    simulation
+}
+
+// Asserts E and B of `latice` match those of `reference` `shift` nodes further along x
+fn assert_matches(latice: &Latice, reference: &Latice, shift: usize) {
+    let [size_x, size_y, size_z] = latice.dimensions;
+    for z in 0..size_z {
+        for y in 0..size_y {
+            for x in 0..size_x {
+                let (node, expected) = (&latice[(x, y, z)], &reference[((x + shift) % reference.dimensions[0], y, z)]);
+                for (value, expected) in [(node.e, expected.e), (node.b, expected.b)] {
+                    let difference = (value - expected).norm();
+                    assert!(difference < 1e-4, "{value} against {expected} at {x} {y} {z}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn pulses_cross_periodic_faces_unchanged() {
+    // The same pulse, once in the middle of the latice and once straddling its faces
+    let middle = run(&manifest(1, r#""periodic""#, &pulse(0, 1.0, true)), 30);
+    let straddling = run(&manifest(1, r#""periodic""#, &pulse(DENSITY / 2, 1.0, true)), 30);
+    assert!(middle.latice().cells.iter().any(|node| node.e.norm() > 0.5));
+    assert_matches(middle.latice(), straddling.latice(), DENSITY / 2);
+}
+
+#[test]
+fn bloch_faces_shift_the_phase() {
+    // A quarter turn over the period, so the field of a latice four periods long repeats the one of
+    // a single period times 1, i, -1 and -i. Its real and imaginary parts evolve on their own, from
+    // the real and imaginary parts of the initial pulse.
+    let wavevector = format!(r#"{{"type": "bloch", "wavevector": {FRAC_PI_2}}}"#);
+    let bloch = run(&manifest(1, &wavevector, &pulse(0, 1.0, false)), 60);
+    let repeated = |phases: [f32; 4]| -> Vec<(usize, f32, f32)> {
+        phases.iter().enumerate().flat_map(|(period, &phase)| {
+            pulse(0, phase, false).into_iter().map(move |(x, e, b)| (x + period * DENSITY, e, b))
+        }).collect()
+    };
+    let real = run(&manifest(4, r#""periodic""#, &repeated([1.0, 0.0, -1.0, 0.0])), 60);
+    let imaginary = run(&manifest(4, r#""periodic""#, &repeated([0.0, 1.0, 0.0, -1.0])), 60);
+    assert_matches(bloch.latice(), real.latice(), 0);
+    assert_matches(bloch.imaginary_latice().unwrap(), imaginary.latice(), 0);
+    // By then the pulse has crossed into the neighbouring periods
+    assert!(bloch.imaginary_latice().unwrap().cells.iter().any(|node| node.e.norm() > 0.1));
+}
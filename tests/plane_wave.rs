@@ -0,0 +1,72 @@
+use maximillion::Simulation;
+use maximillion::manifest::Manifest;
+
+// Oblique plane wave injected through the surface of a box in empty space, which should leave
+// the scattered field outside the box empty
+const PLANE_WAVE: &str = r#"{
+    "constants": {
+        "e0": 1,
+        "m0": 1,
+        "dt": 0.02,
+        "steps": 1,
+        "time_culling_factor": 1,
+        "space_culling_factor": 1,
+        "boundary_condition": {"type": "cpml", "thickness": 6},
+        "solver": "yee",
+        "latice_density": 20,
+        "simulation_side_length": 1.5
+    },
+    "objects": [
+        {
+            "type": "plane_wave",
+            "min": 0.5,
+            "max": 1,
+            "direction": [1, 0.5, 0.25],
+            "polarization": [0, 0, 1],
+            "amplitude": 1,
+            "waveform": {"type": "modulated_gaussian", "center_frequency": 6, "bandwidth": 3}
+        }
+    ]
+}"#;
+
+#[test]
+fn scattered_field_stays_empty() {
+    let manifest: Manifest = serde_json::from_str(PLANE_WAVE).unwrap();
+    let mut simulation = Simulation::from_manifest(&manifest).unwrap();
+    let [size, _, _] = simulation.dimensions();
+    // Nodes 10 to 20 are in the total field, the scattered field keeps a node clear of the
+    // surface and stays out of the CPML
+    let region = |n: usize| {
+        if (10..=20).contains(&n) {
+            Some(true)
+        } else if (7..=8).contains(&n) || (22..size - 6).contains(&n) {
+            Some(false)
+        } else {
+            None
+        }
+    };
+    let (mut total, mut scattered): (f32, f32) = (0.0, 0.0);
+    for _ in 0..300 {
+        simulation.step();
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let e = simulation.e((x, y, z)).norm();
+                    match [x, y, z].map(region) {
+                        [Some(true), Some(true), Some(true)] => total = total.max(e),
+                        regions if regions.contains(&Some(false)) && !regions.contains(&None) => {
+                            scattered = scattered.max(e)
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+    // The incident field is only known approximately on the grid, so a little leaks out.
+    assert!(total > 0.5, "the plane wave never reached the box, {total}");
+    assert!(
+        scattered < 0.02 * total,
+        "{scattered} leaked out of the box against {total} inside it"
+    );
+}
@@ -0,0 +1,59 @@
+use maximillion::Simulation;
+use maximillion::manifest::Manifest;
+
+
+// Two parallel wires driven at different frequencies, the faster one weaker so that neither drowns
+// out the other on its own nodes
+const TWO_WIRES: &str = r#"{
+    "constants": {
+        "e0": 1,
+        "m0": 1,
+        "dt": 0.02,
+        "steps": 1,
+        "time_culling_factor": 1,
+        "space_culling_factor": 1,
+        "boundary_condition": {"type": "cpml", "thickness": 5},
+        "solver": "yee",
+        "latice_density": 20,
+        "simulation_side_length": [2, 1, 1]
+    },
+    "objects": [
+        {"type": "wire", "axis": "z", "location": [12, 10], "amplitude": 1, "angular_frequency": 12},
+        {"type": "wire", "axis": "z", "location": [28, 10], "amplitude": 0.4, "angular_frequency": 30}
+    ]
+}"#;
+
+// Magnitude of the Hann-windowed discrete Fourier transform of `samples` at an angular frequency
+fn spectral_amplitude(samples: &[(f32, f32)], angular_frequency: f32) -> f32 {
+    let n = samples.len() as f32;
+    let (real, imaginary) = samples.iter().enumerate().fold((0.0, 0.0), |(real, imaginary), (i, &(t, value))| {
+        let window = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n).cos();
+        let (sin, cos) = (angular_frequency * t).sin_cos();
+        (real + window * value * cos, imaginary - window * value * sin)
+    });
+    (real * real + imaginary * imaginary).sqrt()
+}
+
+#[test]
+fn each_wire_radiates_at_its_own_frequency() {
+    let manifest: Manifest = serde_json::from_str(TWO_WIRES).unwrap();
+    let mut simulation = Simulation::from_manifest(&manifest).unwrap();
+
+    // One source per wire on top of the vacuum, each driving a whole column of nodes
+    assert_eq!(simulation.sources().len(), 3);
+    assert_eq!(simulation.sources().cells(1).len(), 20);
+    assert_eq!(simulation.sources().cells(2).len(), 20);
+
+    let probes = [(12, 10, 10), (28, 10, 10)];
+    let mut samples = [Vec::new(), Vec::new()];
+    for _ in 0..600 {
+        simulation.step();
+        for (probe, samples) in probes.iter().zip(samples.iter_mut()) {
+            samples.push((simulation.time(), simulation.e(*probe).components[2]));
+        }
+    }
+
+    let [near_first, near_second] = samples;
+    assert!(spectral_amplitude(&near_first, 12.0) > 3.0 * spectral_amplitude(&near_first, 30.0));
+    assert!(spectral_amplitude(&near_second, 30.0) > 3.0 * spectral_amplitude(&near_second, 12.0));
+}
@@ -0,0 +1,39 @@
+use maximillion::Simulation;
+use maximillion::manifest::Manifest;
+
+mod common;
+
+
+// Closed box of perfect conductor with a kick of Ez in the middle, nothing to lose energy to
+const CAVITY: &str = r#"{
+    "constants": {
+        "e0": 1,
+        "m0": 1,
+        "dt": 0.02,
+        "steps": 1,
+        "time_culling_factor": 1,
+        "space_culling_factor": 1,
+        "boundary_condition": "pec",
+        "solver": "yee",
+        "latice_density": 20,
+        "simulation_side_length": 1
+    },
+    "objects": [
+        {"type": "point", "location": [10, 10, 10], "E": [0, 0, 1], "B": [0, 0, 0]},
+        {"type": "point", "location": [7, 12, 9], "E": [0.5, 0, 0], "B": [0, 0.3, 0]}
+    ]
+}"#;
+
+#[test]
+fn cavity_energy_stays_bounded() {
+    let manifest: Manifest = serde_json::from_str(CAVITY).unwrap();
+    let mut simulation = Simulation::from_manifest(&manifest).unwrap();
+    simulation.step();
+    // E and B are half a step apart, so the energy wobbles a little around its initial value
+    let initial = common::energy(&simulation, 1.0, 1.0);
+    for _ in 0..400 {
+        simulation.step();
+        let energy = common::energy(&simulation, 1.0, 1.0);
+        assert!(energy.is_finite() && energy < 1.5 * initial && energy > 0.5 * initial, "energy {energy} from {initial}");
+    }
+}